@@ -2,7 +2,10 @@ use std::{
   borrow::Cow,
   ops::Deref,
   path::{Path, PathBuf},
-  sync::Mutex,
+  sync::{
+    atomic::{AtomicUsize, Ordering},
+    Mutex,
+  },
 };
 
 use crate::{
@@ -28,6 +31,24 @@ pub struct Cache<Fs> {
   is_file_cache: DashMap<PathBuf, bool>,
   is_dir_cache: DashMap<PathBuf, bool>,
   realpath_cache: DashMap<PathBuf, Option<PathBuf>>,
+  // Lookups answered from the caches above, and lookups that had to go to the file system.
+  hits: AtomicUsize,
+  misses: AtomicUsize,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CacheStats {
+  pub hits: usize,
+  pub misses: usize,
+}
+
+impl CacheStats {
+  pub fn hit_rate(&self) -> f64 {
+    match self.hits + self.misses {
+      0 => 0.0,
+      total => self.hits as f64 / total as f64,
+    }
+  }
 }
 
 // Special Cow implementation for a Cache that doesn't require Clone.
@@ -76,14 +97,42 @@ impl<Fs: FileSystem> Cache<Fs> {
       is_file_cache: DashMap::new(),
       is_dir_cache: DashMap::new(),
       realpath_cache: DashMap::new(),
+      hits: AtomicUsize::new(0),
+      misses: AtomicUsize::new(0),
+    }
+  }
+
+  /// Counts of the `is_file`, `is_dir`, package.json and tsconfig.json lookups so far.
+  pub fn stats(&self) -> CacheStats {
+    CacheStats {
+      hits: self.hits.load(Ordering::Relaxed),
+      misses: self.misses.load(Ordering::Relaxed),
+    }
+  }
+
+  fn record(&self, hit: bool) {
+    let counter = if hit { &self.hits } else { &self.misses };
+    counter.fetch_add(1, Ordering::Relaxed);
+  }
+
+  /// Forgets whether `path` and the directories it's in exist, and what they resolve to, e.g.
+  /// after `path` was created or deleted. Parsed package.json and tsconfig.json files are kept,
+  /// so a change to one of those needs a new cache.
+  pub fn invalidate(&self, path: &Path) {
+    for path in path.ancestors() {
+      self.is_file_cache.remove(path);
+      self.is_dir_cache.remove(path);
+      self.realpath_cache.remove(path);
     }
   }
 
   pub fn is_file(&self, path: &Path) -> bool {
     if let Some(is_file) = self.is_file_cache.get(path) {
+      self.record(true);
       return *is_file;
     }
 
+    self.record(false);
     let is_file = self.fs.is_file(path);
     self.is_file_cache.insert(path.to_path_buf(), is_file);
     is_file
@@ -91,9 +140,11 @@ impl<Fs: FileSystem> Cache<Fs> {
 
   pub fn is_dir(&self, path: &Path) -> bool {
     if let Some(is_file) = self.is_dir_cache.get(path) {
+      self.record(true);
       return *is_file;
     }
 
+    self.record(false);
     let is_file = self.fs.is_dir(path);
     self.is_dir_cache.insert(path.to_path_buf(), is_file);
     is_file
@@ -105,8 +156,10 @@ impl<Fs: FileSystem> Cache<Fs> {
 
   pub fn read_package<'a>(&'a self, path: Cow<Path>) -> Result<&'a PackageJson<'a>, ResolverError> {
     if let Some(pkg) = self.packages.get(path.as_ref()) {
+      self.record(true);
       return clone_result(pkg);
     }
+    self.record(false);
 
     fn read_package<Fs: FileSystem>(
       fs: &Fs,
@@ -156,8 +209,10 @@ impl<Fs: FileSystem> Cache<Fs> {
     process: F,
   ) -> Result<&'a TsConfigWrapper<'a>, ResolverError> {
     if let Some(tsconfig) = self.tsconfigs.get(path) {
+      self.record(true);
       return clone_result(tsconfig);
     }
+    self.record(false);
 
     fn read_tsconfig<
       'a,
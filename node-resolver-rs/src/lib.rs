@@ -9,7 +9,7 @@ use std::{
 };
 
 use package_json::{AliasValue, ExportsResolution, PackageJson};
-use tsconfig::TsConfig;
+pub use tsconfig::TsConfig;
 
 mod builtins;
 mod cache;
@@ -22,7 +22,7 @@ mod specifier;
 mod tsconfig;
 mod url_to_path;
 
-pub use cache::{Cache, CacheCow};
+pub use cache::{Cache, CacheCow, CacheStats};
 pub use error::ResolverError;
 pub use fs::FileSystem;
 #[cfg(not(target_arch = "wasm32"))]
@@ -268,6 +268,105 @@ impl<'a, Fs: FileSystem> Resolver<'a, Fs> {
     Ok(ModuleType::CommonJs)
   }
 
+  /// Reads the tsconfig.json at `path`, merged with the configs it extends.
+  pub fn read_tsconfig(
+    &self,
+    path: PathBuf,
+    invalidations: &Invalidations,
+  ) -> Result<&TsConfig<'_>, ResolverError> {
+    let tsconfig = invalidations.read(&path, || {
+      self.cache.read_tsconfig(&path, |tsconfig| {
+        for i in 0..tsconfig.extends.len() {
+          let path = match &tsconfig.extends[i] {
+            Specifier::Absolute(path) => path.as_ref().to_owned(),
+            Specifier::Relative(path) => {
+              let mut absolute_path = resolve_path(&tsconfig.compiler_options.path, path);
+
+              // TypeScript allows "." and ".." to implicitly refer to a tsconfig.json file.
+              if path == Path::new(".") || path == Path::new("..") {
+                absolute_path.push("tsconfig.json");
+              }
+
+              let mut exists = self.cache.fs.is_file(&absolute_path);
+
+              // If the file doesn't exist, and doesn't end with `.json`, try appending the extension.
+              if !exists {
+                let try_extension = match absolute_path.extension() {
+                  None => true,
+                  Some(ext) => ext != "json",
+                };
+
+                if try_extension {
+                  let mut os_str = absolute_path.into_os_string();
+                  os_str.push(".json");
+                  absolute_path = PathBuf::from(os_str);
+                  exists = self.cache.fs.is_file(&absolute_path)
+                }
+              }
+
+              if !exists {
+                return Err(ResolverError::TsConfigExtendsNotFound {
+                  tsconfig: tsconfig.compiler_options.path.clone(),
+                  error: Box::new(ResolverError::FileNotFound {
+                    relative: path.to_path_buf(),
+                    from: tsconfig.compiler_options.path.clone(),
+                  }),
+                });
+              }
+
+              absolute_path
+            }
+            specifier @ Specifier::Package(..) => {
+              let resolver = Resolver {
+                project_root: Cow::Borrowed(&self.project_root),
+                extensions: Extensions::Borrowed(&["json"]),
+                index_file: "tsconfig.json",
+                entries: Fields::TSCONFIG,
+                flags: Flags::NODE_CJS,
+                cache: CacheCow::Borrowed(&self.cache),
+                include_node_modules: Cow::Borrowed(self.include_node_modules.as_ref()),
+                conditions: ExportsCondition::TYPES,
+                module_dir_resolver: self.module_dir_resolver.clone(),
+              };
+
+              let req = ResolveRequest::new(
+                &resolver,
+                specifier,
+                SpecifierType::Cjs,
+                &tsconfig.compiler_options.path,
+                invalidations,
+              );
+
+              let res = req
+                .resolve()
+                .map_err(|err| ResolverError::TsConfigExtendsNotFound {
+                  tsconfig: tsconfig.compiler_options.path.clone(),
+                  error: Box::new(err),
+                })?;
+
+              if let Resolution::Path(res) = res {
+                res
+              } else {
+                return Err(ResolverError::TsConfigExtendsNotFound {
+                  tsconfig: tsconfig.compiler_options.path.clone(),
+                  error: Box::new(ResolverError::UnknownError),
+                });
+              }
+            }
+            _ => return Ok(()),
+          };
+
+          let extended = self.read_tsconfig(path, invalidations)?;
+          tsconfig.compiler_options.extend(extended);
+        }
+
+        Ok(())
+      })
+    })?;
+
+    Ok(&tsconfig.compiler_options)
+  }
+
   fn find_package(
     &self,
     from: &Path,
@@ -1095,97 +1194,7 @@ impl<'a, Fs: FileSystem> ResolveRequest<'a, Fs> {
   }
 
   fn read_tsconfig(&self, path: PathBuf) -> Result<&'a TsConfig<'a>, ResolverError> {
-    let tsconfig = self.invalidations.read(&path, || {
-      self.resolver.cache.read_tsconfig(&path, |tsconfig| {
-        for i in 0..tsconfig.extends.len() {
-          let path = match &tsconfig.extends[i] {
-            Specifier::Absolute(path) => path.as_ref().to_owned(),
-            Specifier::Relative(path) => {
-              let mut absolute_path = resolve_path(&tsconfig.compiler_options.path, path);
-
-              // TypeScript allows "." and ".." to implicitly refer to a tsconfig.json file.
-              if path == Path::new(".") || path == Path::new("..") {
-                absolute_path.push("tsconfig.json");
-              }
-
-              let mut exists = self.resolver.cache.fs.is_file(&absolute_path);
-
-              // If the file doesn't exist, and doesn't end with `.json`, try appending the extension.
-              if !exists {
-                let try_extension = match absolute_path.extension() {
-                  None => true,
-                  Some(ext) => ext != "json",
-                };
-
-                if try_extension {
-                  let mut os_str = absolute_path.into_os_string();
-                  os_str.push(".json");
-                  absolute_path = PathBuf::from(os_str);
-                  exists = self.resolver.cache.fs.is_file(&absolute_path)
-                }
-              }
-
-              if !exists {
-                return Err(ResolverError::TsConfigExtendsNotFound {
-                  tsconfig: tsconfig.compiler_options.path.clone(),
-                  error: Box::new(ResolverError::FileNotFound {
-                    relative: path.to_path_buf(),
-                    from: tsconfig.compiler_options.path.clone(),
-                  }),
-                });
-              }
-
-              absolute_path
-            }
-            specifier @ Specifier::Package(..) => {
-              let resolver = Resolver {
-                project_root: Cow::Borrowed(&self.resolver.project_root),
-                extensions: Extensions::Borrowed(&["json"]),
-                index_file: "tsconfig.json",
-                entries: Fields::TSCONFIG,
-                flags: Flags::NODE_CJS,
-                cache: CacheCow::Borrowed(&self.resolver.cache),
-                include_node_modules: Cow::Borrowed(self.resolver.include_node_modules.as_ref()),
-                conditions: ExportsCondition::TYPES,
-                module_dir_resolver: self.resolver.module_dir_resolver.clone(),
-              };
-
-              let req = ResolveRequest::new(
-                &resolver,
-                specifier,
-                SpecifierType::Cjs,
-                &tsconfig.compiler_options.path,
-                self.invalidations,
-              );
-
-              let res = req
-                .resolve()
-                .map_err(|err| ResolverError::TsConfigExtendsNotFound {
-                  tsconfig: tsconfig.compiler_options.path.clone(),
-                  error: Box::new(err),
-                })?;
-
-              if let Resolution::Path(res) = res {
-                res
-              } else {
-                return Err(ResolverError::TsConfigExtendsNotFound {
-                  tsconfig: tsconfig.compiler_options.path.clone(),
-                  error: Box::new(ResolverError::UnknownError),
-                });
-              }
-            }
-            _ => return Ok(()),
-          };
-
-          let extended = self.read_tsconfig(path)?;
-          tsconfig.compiler_options.extend(extended);
-        }
-
-        Ok(())
-      })
-    })?;
-
-    Ok(&tsconfig.compiler_options)
+    self.resolver.read_tsconfig(path, self.invalidations)
   }
 }
 
@@ -20,7 +20,16 @@ pub struct TsConfig<'a> {
   #[serde(skip)]
   paths_base: PathBuf,
   pub module_suffixes: Option<Vec<&'a str>>,
+  out_dir: Option<Cow<'a, Path>>,
   // rootDirs??
+  /// The top-level `files`, `include` and `exclude`, resolved against the tsconfig that set
+  /// them. Kept here since they're inherited through `extends` like the compiler options.
+  #[serde(skip)]
+  files: Option<Vec<PathBuf>>,
+  #[serde(skip)]
+  include: Option<Vec<PathBuf>>,
+  #[serde(skip)]
+  exclude: Option<Vec<PathBuf>>,
 }
 
 fn deserialize_extends<'a, 'de: 'a, D>(deserializer: D) -> Result<Vec<Specifier<'a>>, D::Error>
@@ -50,12 +59,29 @@ pub struct TsConfigWrapper<'a> {
   pub extends: Vec<Specifier<'a>>,
   #[serde(default)]
   pub compiler_options: TsConfig<'a>,
+  #[serde(default)]
+  files: Option<Vec<String>>,
+  #[serde(default)]
+  include: Option<Vec<String>>,
+  #[serde(default)]
+  exclude: Option<Vec<String>>,
 }
 
 impl<'a> TsConfig<'a> {
   pub fn parse(path: PathBuf, data: &'a mut str) -> serde_json::Result<TsConfigWrapper<'a>> {
     let _ = strip_comments_in_place(data, Default::default(), true);
     let mut wrapper: TsConfigWrapper = serde_json::from_str(data)?;
+    let resolve = |patterns: Option<Vec<String>>| {
+      patterns.map(|patterns| {
+        patterns
+          .iter()
+          .map(|pattern| resolve_path(&path, pattern))
+          .collect()
+      })
+    };
+    wrapper.compiler_options.files = resolve(wrapper.files.take());
+    wrapper.compiler_options.include = resolve(wrapper.include.take());
+    wrapper.compiler_options.exclude = resolve(wrapper.exclude.take());
     wrapper.compiler_options.path = path;
     wrapper.compiler_options.validate();
     Ok(wrapper)
@@ -66,6 +92,10 @@ impl<'a> TsConfig<'a> {
       *base_url = Cow::Owned(resolve_path(&self.path, &base_url));
     }
 
+    if let Some(out_dir) = &mut self.out_dir {
+      *out_dir = Cow::Owned(resolve_path(&self.path, &out_dir));
+    }
+
     if self.paths.is_some() {
       self.paths_base = if let Some(base_url) = &self.base_url {
         base_url.as_ref().to_owned()
@@ -88,6 +118,66 @@ impl<'a> TsConfig<'a> {
     if self.module_suffixes.is_none() {
       self.module_suffixes = extended.module_suffixes.clone();
     }
+
+    if self.out_dir.is_none() {
+      self.out_dir = extended.out_dir.clone();
+    }
+
+    if self.files.is_none() {
+      self.files = extended.files.clone();
+    }
+
+    if self.include.is_none() {
+      self.include = extended.include.clone();
+    }
+
+    if self.exclude.is_none() {
+      self.exclude = extended.exclude.clone();
+    }
+  }
+
+  /// Whether `file` is part of the program the tsconfig describes: it's listed in `files`, or
+  /// matches `include` but not `exclude`. Like in TypeScript, `include` defaults to everything
+  /// in the tsconfig's directory unless there are `files`, and `exclude` defaults to
+  /// `node_modules`, `bower_components`, `jspm_packages` and the `outDir`.
+  pub fn includes_file(&self, file: &Path) -> bool {
+    if let Some(files) = &self.files {
+      if files.iter().any(|listed| listed == file) {
+        return true;
+      }
+    }
+
+    let dir = self.path.parent().unwrap_or(Path::new(""));
+    let included = match (&self.include, &self.files) {
+      (Some(include), _) => include.iter().any(|pattern| {
+        // A pattern without wildcards or an extension is a directory.
+        let is_dir = pattern.file_name().map_or(false, |name| {
+          let name = name.to_string_lossy();
+          !name.contains(['*', '?']) && Path::new(name.as_ref()).extension().is_none()
+        });
+        match is_dir {
+          true => file.starts_with(pattern),
+          false => glob_matches(pattern, file),
+        }
+      }),
+      (None, Some(_)) => false,
+      (None, None) => file.starts_with(dir),
+    };
+    if !included {
+      return false;
+    }
+
+    // Excluding a directory excludes everything in it.
+    let mut paths = file.ancestors();
+    match &self.exclude {
+      Some(exclude) => !paths.any(|path| exclude.iter().any(|pattern| glob_matches(pattern, path))),
+      None => !paths.take_while(|path| *path != dir).any(|path| {
+        self.out_dir.as_deref() == Some(path)
+          || path.file_name().map_or(false, |name| {
+            name == "node_modules" || name == "bower_components" || name == "jspm_packages"
+          })
+      }),
+    }
   }
 
   pub fn paths(&'a self, specifier: &'a Specifier) -> impl Iterator<Item = PathBuf> + 'a {
@@ -152,6 +242,10 @@ impl<'a> TsConfig<'a> {
   }
 }
 
+fn glob_matches(pattern: &Path, path: &Path) -> bool {
+  pattern == path || glob_match::glob_match(&pattern.to_string_lossy(), &path.to_string_lossy())
+}
+
 fn join_paths<'a>(
   base_url: &'a Path,
   paths: &'a [&'a str],
@@ -303,4 +397,68 @@ mod tests {
     );
     assert_eq!(test("./jquery"), Vec::<PathBuf>::new());
   }
+
+  #[test]
+  fn test_includes_file() {
+    let parse = |path: &str, data: &str| {
+      let data = Box::leak(data.to_owned().into_boxed_str());
+      TsConfig::parse(path.into(), data).unwrap().compiler_options
+    };
+
+    let tsconfig = parse(
+      "/foo/tsconfig.json",
+      r#"{
+        "files": ["scripts/build.ts"],
+        "include": ["src", "types/*.d.ts"],
+        "exclude": ["src/**/*.test.ts", "src/fixtures"],
+      }"#,
+    );
+    let test = |file: &str| tsconfig.includes_file(Path::new(file));
+    assert!(test("/foo/src/index.ts"));
+    assert!(test("/foo/src/nested/index.ts"));
+    assert!(!test("/foo/src/index.test.ts"));
+    assert!(!test("/foo/src/fixtures/index.ts"));
+    assert!(test("/foo/types/env.d.ts"));
+    assert!(!test("/foo/types/nested/env.d.ts"));
+    assert!(test("/foo/scripts/build.ts"));
+    assert!(!test("/foo/scripts/release.ts"));
+
+    // Listed files aren't excluded, and `exclude` replaces the default one.
+    let tsconfig = parse(
+      "/foo/tsconfig.json",
+      r#"{
+        "files": ["src/generated/schema.ts"],
+        "include": ["src"],
+        "exclude": ["src/generated"],
+      }"#,
+    );
+    let test = |file: &str| tsconfig.includes_file(Path::new(file));
+    assert!(test("/foo/src/generated/schema.ts"));
+    assert!(!test("/foo/src/generated/types.ts"));
+    assert!(test("/foo/src/node_modules/index.ts"));
+
+    // Patterns are relative to the tsconfig that sets them.
+    let mut tsconfig = parse("/foo/packages/a/tsconfig.json", "{}");
+    tsconfig.extend(&parse(
+      "/foo/tsconfig.base.json",
+      r#"{ "include": ["shared"], "exclude": ["**/*.test.ts"] }"#,
+    ));
+    assert!(tsconfig.includes_file(Path::new("/foo/shared/index.ts")));
+    assert!(!tsconfig.includes_file(Path::new("/foo/shared/index.test.ts")));
+    assert!(!tsconfig.includes_file(Path::new("/foo/packages/a/index.ts")));
+
+    let tsconfig = parse(
+      "/foo/tsconfig.json",
+      r#"{ "compilerOptions": { "outDir": "dist" } }"#,
+    );
+    let test = |file: &str| tsconfig.includes_file(Path::new(file));
+    assert!(test("/foo/index.ts"));
+    assert!(!test("/foo/dist/index.js"));
+    assert!(!test("/foo/node_modules/dep/index.ts"));
+    assert!(!test("/bar/index.ts"));
+
+    // Only the directories in the tsconfig's are excluded by default.
+    let tsconfig = parse("/foo/node_modules/dep/tsconfig.json", "{}");
+    assert!(tsconfig.includes_file(Path::new("/foo/node_modules/dep/index.ts")));
+  }
 }
@@ -0,0 +1,154 @@
+use std::collections::HashSet;
+use std::fs;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::report::{relative_path, FindingKind};
+use crate::AnalysisResults;
+
+const BASELINE_VERSION: u32 = 1;
+
+// A finding, identified by its file, symbol and kind rather than its position, so it survives
+// edits elsewhere in the file. `kind` is a `FindingKind` id, e.g. `unused-export`.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct BaselineEntry {
+    // Relative to the repo root
+    pub file: String,
+    // The exported name, imported name or import specifier. Empty for unused files.
+    pub symbol: String,
+    pub kind: String,
+}
+
+impl BaselineEntry {
+    pub fn new(root: &Path, file: &Path, symbol: &str, kind: FindingKind) -> Self {
+        Self {
+            file: relative_path(root, file).unwrap_or_else(|| file.display().to_string()),
+            symbol: symbol.into(),
+            kind: kind.id().into(),
+        }
+    }
+}
+
+// Known findings, which aren't reported again so that only new dead code fails CI.
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct Baseline {
+    pub version: u32,
+    pub entries: Vec<BaselineEntry>,
+}
+
+impl Baseline {
+    // A baseline of every finding in `results`.
+    pub fn new(results: &AnalysisResults, root: &Path) -> Self {
+        let mut entries = Vec::new();
+        for (file, module_results) in &results.modules {
+            for export in &module_results.unused_exports {
+                entries.push(BaselineEntry::new(
+                    root,
+                    file,
+                    &export.exported_name,
+                    FindingKind::UnusedExport,
+                ));
+            }
+            for export in &module_results.unused_type_exports {
+                entries.push(BaselineEntry::new(
+                    root,
+                    file,
+                    &export.exported_name,
+                    FindingKind::UnusedTypeExport,
+                ));
+            }
+            for export in &module_results.test_only_exports {
+                entries.push(BaselineEntry::new(
+                    root,
+                    file,
+                    &export.exported_name,
+                    FindingKind::TestOnlyExport,
+                ));
+            }
+        }
+        for file in &results.unused_files {
+            entries.push(BaselineEntry::new(root, file, "", FindingKind::UnusedFile));
+        }
+        for missing in &results.missing_exports {
+            entries.push(BaselineEntry::new(
+                root,
+                &missing.importer,
+                &missing.symbol,
+                FindingKind::MissingExport,
+            ));
+        }
+        for unresolved in &results.unresolved_imports {
+            entries.push(BaselineEntry::new(
+                root,
+                &unresolved.importer,
+                &unresolved.specifier,
+                FindingKind::UnresolvedImport,
+            ));
+        }
+        for stale in &results.stale_suppressions {
+            entries.push(BaselineEntry::new(
+                root,
+                &stale.file,
+                &stale.directive,
+                FindingKind::StaleSuppression,
+            ));
+        }
+        entries.sort();
+        entries.dedup();
+
+        Self {
+            version: BASELINE_VERSION,
+            entries,
+        }
+    }
+
+    pub fn read(path: &Path) -> Result<Self, String> {
+        let contents = fs::read_to_string(path).map_err(|err| err.to_string())?;
+        let baseline: Self =
+            serde_json::from_str(&contents).map_err(|err| format!("invalid baseline: {}", err))?;
+        if baseline.version != BASELINE_VERSION {
+            return Err(format!("unsupported baseline version {}", baseline.version));
+        }
+        Ok(baseline)
+    }
+
+    pub fn write(&self, path: &Path) -> Result<(), String> {
+        let json = serde_json::to_string_pretty(self).map_err(|err| err.to_string())?;
+        fs::write(path, json + "\n").map_err(|err| err.to_string())
+    }
+
+    // Removes the findings in the baseline from `results`. Returns the entries that no longer
+    // match a finding, so the baseline can be tightened.
+    pub fn apply(&self, results: &mut AnalysisResults, root: &Path) -> Vec<BaselineEntry> {
+        let mut matcher = Matcher {
+            root,
+            entries: self.entries.iter().collect(),
+            matched: HashSet::new(),
+        };
+        results.retain(|file, symbol, kind| !matcher.matches(file, symbol, kind));
+
+        self.entries
+            .iter()
+            .filter(|entry| !matcher.matched.contains(entry))
+            .cloned()
+            .collect()
+    }
+}
+
+struct Matcher<'a> {
+    root: &'a Path,
+    entries: HashSet<&'a BaselineEntry>,
+    matched: HashSet<BaselineEntry>,
+}
+
+impl Matcher<'_> {
+    fn matches(&mut self, file: &Path, symbol: &str, kind: FindingKind) -> bool {
+        let entry = BaselineEntry::new(self.root, file, symbol, kind);
+        if !self.entries.contains(&entry) {
+            return false;
+        }
+        self.matched.insert(entry);
+        true
+    }
+}
@@ -0,0 +1,618 @@
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+use parcel_resolver::{
+    FileCreateInvalidation, FileSystem, Invalidations, ResolveOptions, Resolver,
+};
+use serde::{Deserialize, Serialize};
+use xxhash_rust::xxh3::Xxh3;
+
+use crate::{resolve_specifier, FileFacts, Resolved};
+
+// Bump when what `FileAnalyzer` records changes, so facts collected by older versions aren't
+// reused.
+const FACTS_VERSION: u32 = 4;
+
+// The facts collected from each file, kept on disk between runs so that unchanged files aren't
+// parsed again. Cached facts are reused if the file's contents and resolver configuration are
+// the same, and its imports still resolve to the same files.
+#[derive(Serialize, Deserialize)]
+pub struct FactsCache {
+    version: u32,
+    entries: HashMap<PathBuf, CacheEntry>,
+    // Files looked up since the cache was read. Only these are written back, which drops the
+    // entries of deleted files.
+    #[serde(skip)]
+    used: HashSet<PathBuf>,
+    #[serde(skip)]
+    stats: FactsCacheStats,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct FactsCacheStats {
+    // Files whose cached facts were reused
+    pub reused: usize,
+    // Files that were parsed and analyzed
+    pub analyzed: usize,
+    // Specifiers of reused files that were resolved again, since a file they depend on changed
+    pub reresolved: usize,
+}
+
+impl Default for FactsCache {
+    fn default() -> Self {
+        Self {
+            version: FACTS_VERSION,
+            entries: HashMap::new(),
+            used: HashSet::new(),
+            stats: FactsCacheStats::default(),
+        }
+    }
+}
+
+impl FactsCache {
+    // Reads the cache at `path`. A missing, corrupt or outdated cache is treated as empty, since
+    // it can always be rebuilt.
+    pub fn read(path: &Path) -> Self {
+        fs::read_to_string(path)
+            .ok()
+            .and_then(|contents| serde_json::from_str::<Self>(&contents).ok())
+            .filter(|cache| cache.version == FACTS_VERSION)
+            .unwrap_or_default()
+    }
+
+    pub fn write(&mut self, path: &Path) -> Result<(), String> {
+        let used = &self.used;
+        self.entries.retain(|file, _| used.contains(file));
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir).map_err(|err| err.to_string())?;
+        }
+        // Write to a temporary file first, so an interrupted run can't leave a truncated cache.
+        let json = serde_json::to_string(self).map_err(|err| err.to_string())?;
+        let temporary = path.with_extension("tmp");
+        fs::write(&temporary, json).map_err(|err| err.to_string())?;
+        fs::rename(&temporary, path).map_err(|err| err.to_string())
+    }
+
+    pub fn stats(&self) -> FactsCacheStats {
+        self.stats
+    }
+
+    // Collects the facts of `file_path`, whose contents are `source`, reusing the cached ones if
+    // they're still valid. Only reads the cache, so it can run in parallel; `store` records the
+    // outcome.
+    pub(crate) fn lookup<FS: FileSystem>(
+        &self,
+        resolver: &Resolver<'_, FS>,
+        file_path: &Path,
+        source: io::Result<String>,
+        resolve_options: &ResolveOptions,
+    ) -> Lookup {
+        let Ok(contents) = &source else {
+            // The file can't be read, which is reported by `FileFacts::collect`.
+            let facts = FileFacts::collect(resolver, file_path, source, resolve_options);
+            return Lookup::analyzed(facts, None);
+        };
+        let key = cache_key(resolver, resolve_options, contents);
+
+        if let Some(entry) = self.entries.get(file_path) {
+            if entry.key == key {
+                if let Some(lookup) = entry.revalidate(resolver, file_path, resolve_options) {
+                    return lookup;
+                }
+            }
+        }
+
+        let facts = FileFacts::collect(resolver, file_path, source, resolve_options);
+        let entry = CacheEntry::new(key, facts.clone());
+        Lookup::analyzed(facts, Some(entry))
+    }
+
+    // Records the outcome of `lookup` and returns the facts.
+    pub(crate) fn store(&mut self, file_path: &Path, lookup: Lookup) -> FileFacts {
+        match lookup.reresolved {
+            Some(reresolved) => {
+                self.stats.reused += 1;
+                self.stats.reresolved += reresolved;
+            }
+            None => self.stats.analyzed += 1,
+        }
+        if let Some(entry) = lookup.entry {
+            self.entries.insert(file_path.into(), entry);
+        }
+        self.used.insert(file_path.into());
+        lookup.facts
+    }
+}
+
+pub(crate) struct Lookup {
+    facts: FileFacts,
+    // The new cache entry, if it changed
+    entry: Option<CacheEntry>,
+    // The number of specifiers resolved again, if the cached facts were reused
+    reresolved: Option<usize>,
+}
+
+impl Lookup {
+    fn analyzed(facts: FileFacts, entry: Option<CacheEntry>) -> Self {
+        Self {
+            facts,
+            entry,
+            reresolved: None,
+        }
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+struct CacheEntry {
+    // Hash of the file's contents and the resolver configuration
+    key: u64,
+    facts: FileFacts,
+    // file -> its state when the facts were collected, for the files the resolutions depend on
+    stamps: HashMap<PathBuf, Option<Stamp>>,
+}
+
+impl CacheEntry {
+    fn new(key: u64, facts: FileFacts) -> Self {
+        let stamps = facts
+            .resolutions
+            .iter()
+            .flat_map(|resolution| &resolution.dependencies.changed)
+            .map(|file| (file.clone(), stamp(file)))
+            .collect();
+        Self { key, facts, stamps }
+    }
+
+    // Resolves the specifiers whose dependencies changed again. Returns `None` if any of them
+    // resolves differently now, so the file has to be analyzed again.
+    fn revalidate<FS: FileSystem>(
+        &self,
+        resolver: &Resolver<'_, FS>,
+        file_path: &Path,
+        resolve_options: &ResolveOptions,
+    ) -> Option<Lookup> {
+        let mut changed = Vec::new();
+        for (i, resolution) in self.facts.resolutions.iter().enumerate() {
+            if self.is_fresh(&resolution.dependencies) {
+                continue;
+            }
+            let (resolved, invalidations) =
+                resolve_specifier(resolver, &resolution.specifier, file_path, resolve_options);
+            if resolved != resolution.resolved {
+                return None;
+            }
+            changed.push((i, Dependencies::new(&invalidations, &resolved)));
+        }
+
+        if changed.is_empty() {
+            return Some(Lookup {
+                facts: self.facts.clone(),
+                entry: None,
+                reresolved: Some(0),
+            });
+        }
+        let reresolved = changed.len();
+        let mut facts = self.facts.clone();
+        for (i, dependencies) in changed {
+            facts.resolutions[i].dependencies = dependencies;
+        }
+        Some(Lookup {
+            facts: facts.clone(),
+            entry: Some(CacheEntry::new(self.key, facts)),
+            reresolved: Some(reresolved),
+        })
+    }
+
+    // Whether the files a resolution depends on are as they were when it was cached.
+    fn is_fresh(&self, dependencies: &Dependencies) -> bool {
+        !dependencies.always
+            && dependencies
+                .changed
+                .iter()
+                .all(|file| self.stamps.get(file) == Some(&stamp(file)))
+            && !dependencies.created.iter().any(|file| file.is_file())
+    }
+}
+
+// The files a resolution depends on, from the `Invalidations` of the resolver.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub(crate) struct Dependencies {
+    // Files whose changes may change the resolution, e.g. package.jsons and the resolved file
+    changed: Vec<PathBuf>,
+    // Files whose creation may change the resolution, e.g. `utils.ts` when `./utils` resolved to
+    // `utils/index.ts`
+    created: Vec<PathBuf>,
+    // Whether the resolution has to be checked on every run, e.g. because it depends on files
+    // matching a glob, which we don't track
+    always: bool,
+}
+
+impl Dependencies {
+    // Whether creating, changing or deleting the `changed` files may change the resolution.
+    pub(crate) fn is_affected_by(&self, changed: &BTreeSet<PathBuf>) -> bool {
+        self.always
+            || self
+                .changed
+                .iter()
+                .chain(&self.created)
+                .any(|file| changed.contains(file))
+    }
+
+    pub(crate) fn new(invalidations: &Invalidations, resolved: &Resolved) -> Self {
+        let mut changed: Vec<PathBuf> = invalidations
+            .invalidate_on_file_change
+            .iter()
+            .map(|file| file.key().clone())
+            .collect();
+        // The resolver doesn't track the resolved file, since it assumes it's watched anyway.
+        if let Resolved::File(file) = resolved {
+            changed.push(file.clone());
+        }
+        changed.sort();
+        changed.dedup();
+
+        let mut created = Vec::new();
+        let mut always = invalidations
+            .invalidate_on_startup
+            .load(std::sync::atomic::Ordering::Relaxed);
+        for invalidation in invalidations.invalidate_on_file_create.iter() {
+            match invalidation.key() {
+                FileCreateInvalidation::Path(file) => created.push(file.clone()),
+                // The nearest `file_name` above `above` is used, so creating one closer than
+                // the current one would change the resolution.
+                FileCreateInvalidation::FileName { file_name, above } => {
+                    for dir in above.ancestors() {
+                        let file = dir.join(file_name);
+                        if changed.contains(&file) {
+                            break;
+                        }
+                        created.push(file);
+                    }
+                }
+                FileCreateInvalidation::Glob(_) => always = true,
+            }
+        }
+        created.sort();
+        created.dedup();
+
+        Self {
+            changed,
+            created,
+            always,
+        }
+    }
+}
+
+// The size and modification time of a file, which change whenever its contents do.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+struct Stamp {
+    len: u64,
+    modified_secs: u64,
+    modified_nanos: u32,
+}
+
+// `None` if the file doesn't exist.
+fn stamp(file: &Path) -> Option<Stamp> {
+    let metadata = fs::metadata(file).ok()?;
+    let modified = metadata
+        .modified()
+        .ok()?
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
+    Some(Stamp {
+        len: metadata.len(),
+        modified_secs: modified.as_secs(),
+        modified_nanos: modified.subsec_nanos(),
+    })
+}
+
+fn cache_key<FS: FileSystem>(
+    resolver: &Resolver<'_, FS>,
+    resolve_options: &ResolveOptions,
+    contents: &str,
+) -> u64 {
+    let configuration = format!(
+        "{}\0{}\0{}\0{}\0{}\0{}\0{:?}\0",
+        FACTS_VERSION,
+        resolver.project_root.display(),
+        resolver.flags.bits(),
+        resolver.entries.bits(),
+        resolver.conditions.bits(),
+        resolve_options.conditions.bits(),
+        resolve_options.custom_conditions,
+    );
+    let mut hasher = Xxh3::new();
+    hasher.update(configuration.as_bytes());
+    hasher.update(contents.as_bytes());
+    hasher.digest()
+}
+
+// Serializes a map as a list of pairs, for maps whose keys aren't strings, e.g. `Owner`s.
+pub(crate) mod pairs {
+    use std::collections::HashMap;
+    use std::hash::Hash;
+
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    pub fn serialize<K: Serialize, V: Serialize, S: Serializer>(
+        map: &HashMap<K, V>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(map)
+    }
+
+    pub fn deserialize<'de, K, V, D>(deserializer: D) -> Result<HashMap<K, V>, D::Error>
+    where
+        K: Deserialize<'de> + Eq + Hash,
+        V: Deserialize<'de>,
+        D: Deserializer<'de>,
+    {
+        let pairs = Vec::<(K, V)>::deserialize(deserializer)?;
+        Ok(pairs.into_iter().collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixture::TempDir;
+    use crate::Analyzer;
+
+    // The facts of `file` in `dir`, as `lookup` collects them.
+    fn collect(dir: &TempDir, file: &str) -> FileFacts {
+        let resolver = dir.resolver("");
+        let file = dir.join(file);
+        let source = fs::read_to_string(&file);
+        FileFacts::collect(&resolver, &file, source, &Default::default())
+    }
+
+    #[test]
+    fn dependencies() {
+        let invalidations = Invalidations::default();
+        invalidations.invalidate_on_file_change(Path::new("/repo/package.json"));
+        invalidations.invalidate_on_file_create(Path::new("/repo/src/utils.ts"));
+        invalidations.invalidate_on_file_create_above("package.json", Path::new("/repo/src/utils"));
+        let resolved = Resolved::File("/repo/src/utils/index.ts".into());
+        let dependencies = Dependencies::new(&invalidations, &resolved);
+        assert_eq!(
+            dependencies.changed,
+            ["/repo/package.json", "/repo/src/utils/index.ts"].map(PathBuf::from)
+        );
+        // Only package.jsons closer than the one that was used
+        assert_eq!(
+            dependencies.created,
+            [
+                "/repo/src/package.json",
+                "/repo/src/utils/package.json",
+                "/repo/src/utils.ts",
+            ]
+            .map(PathBuf::from)
+        );
+        assert!(!dependencies.always);
+
+        invalidations.invalidate_on_glob_create("/repo/src/*.ts");
+        assert!(Dependencies::new(&invalidations, &resolved).always);
+
+        let invalidations = Invalidations::default();
+        invalidations.invalidate_on_startup();
+        let dependencies = Dependencies::new(&invalidations, &Resolved::Unresolved);
+        assert!(dependencies.changed.is_empty());
+        assert!(dependencies.always);
+    }
+
+    #[test]
+    fn freshness() {
+        let dir = TempDir::new("freshness");
+        dir.write("package.json", "{}");
+        dir.write("index.ts", "import './utils';\nimport './missing';\n");
+        dir.write("utils/index.ts", "");
+        let is_fresh = |entry: &CacheEntry, specifier: &str| {
+            let resolution = entry
+                .facts
+                .resolutions
+                .iter()
+                .find(|resolution| &*resolution.specifier == specifier)
+                .unwrap();
+            entry.is_fresh(&resolution.dependencies)
+        };
+        let entry = CacheEntry::new(0, collect(&dir, "index.ts"));
+        assert!(is_fresh(&entry, "./utils"));
+        assert!(is_fresh(&entry, "./missing"));
+
+        dir.write("missing.ts", "");
+        assert!(!is_fresh(&entry, "./missing"));
+        assert!(is_fresh(&entry, "./utils"));
+
+        dir.write("utils/index.ts", "export {};\n");
+        assert!(!is_fresh(&entry, "./utils"));
+
+        // Deleting a file makes its stamp stale too.
+        let entry = CacheEntry::new(0, collect(&dir, "index.ts"));
+        assert!(is_fresh(&entry, "./utils"));
+        dir.remove("utils/index.ts");
+        assert!(!is_fresh(&entry, "./utils"));
+    }
+
+    #[test]
+    fn read_and_write() {
+        let dir = TempDir::new("cache-file");
+        dir.write("a.ts", "");
+        dir.write("b.ts", "");
+        let path = dir.join("cache/facts.json");
+        assert!(FactsCache::read(&path).entries.is_empty());
+
+        let mut cache = FactsCache::default();
+        for file in ["a.ts", "b.ts"] {
+            let facts = collect(&dir, file);
+            let entry = CacheEntry::new(0, facts.clone());
+            cache.store(&dir.join(file), Lookup::analyzed(facts, Some(entry)));
+        }
+        cache.write(&path).unwrap();
+        assert_eq!(FactsCache::read(&path).entries.len(), 2);
+
+        // Only the files looked up since the cache was read are written back.
+        let mut cache = FactsCache::read(&path);
+        let facts = collect(&dir, "a.ts");
+        cache.store(&dir.join("a.ts"), Lookup::analyzed(facts, None));
+        cache.write(&path).unwrap();
+        let cache = FactsCache::read(&path);
+        assert_eq!(
+            cache.entries.keys().collect::<Vec<_>>(),
+            [&dir.join("a.ts")]
+        );
+
+        let outdated = fs::read_to_string(&path).unwrap().replacen(
+            &format!("\"version\":{}", FACTS_VERSION),
+            &format!("\"version\":{}", FACTS_VERSION - 1),
+            1,
+        );
+        dir.write("cache/facts.json", &outdated);
+        assert!(FactsCache::read(&path).entries.is_empty());
+        dir.write("cache/facts.json", "{");
+        assert!(FactsCache::read(&path).entries.is_empty());
+    }
+
+    #[test]
+    fn lookup() {
+        let dir = TempDir::new("cache");
+        dir.write("package.json", "{}");
+        dir.write(
+            "a.ts",
+            "import { b } from './b';\nimport './c';\nexport const a = b;\n",
+        );
+        dir.write("b.ts", "export const b = 1;\n");
+
+        let analyze_cached = |cache: &mut FactsCache| {
+            let resolver = dir.resolver("");
+            let files = [(&resolver, dir.join("a.ts")), (&resolver, dir.join("b.ts"))];
+            let mut analyzer = Analyzer::new(Default::default());
+            analyzer.add_files_cached(&files, cache);
+            cache.write(&dir.join("cache.json")).unwrap();
+            (analyzer.finalize(), cache.stats())
+        };
+        let stats = |reused, analyzed, reresolved| FactsCacheStats {
+            reused,
+            analyzed,
+            reresolved,
+        };
+
+        let (cold, cold_stats) = analyze_cached(&mut Default::default());
+        assert_eq!(cold_stats, stats(0, 2, 0));
+
+        // Nothing changed
+        let read = || FactsCache::read(&dir.join("cache.json"));
+        let (warm, warm_stats) = analyze_cached(&mut read());
+        assert_eq!(warm_stats, stats(2, 0, 0));
+        assert_eq!(warm, cold);
+
+        // Both specifiers of a.ts depend on package.json, but resolve the same way
+        dir.write("package.json", "{ }");
+        assert_eq!(analyze_cached(&mut read()).1, stats(2, 0, 2));
+
+        dir.write("b.ts", "export const b = 1;\nexport const c = 2;\n");
+        let (changed, changed_stats) = analyze_cached(&mut read());
+        // a.ts is reused, but resolves `./b` again since the file it resolved to changed.
+        assert_eq!(changed_stats, stats(1, 1, 1));
+        let b = &changed.modules[&dir.join("b.ts")];
+        assert_eq!(b.unused_exports.len(), 1);
+        assert_eq!(&*b.unused_exports[0].original_name, "c");
+
+        // `./c` resolves now, so a.ts has to be analyzed again.
+        assert_eq!(changed.unresolved_imports.len(), 1);
+        dir.write("c.ts", "");
+        let (created, created_stats) = analyze_cached(&mut read());
+        assert_eq!(created_stats, stats(1, 1, 0));
+        assert!(created.unresolved_imports.is_empty());
+    }
+
+    #[test]
+    fn affected_by() {
+        let dependencies = Dependencies {
+            changed: vec!["/repo/package.json".into()],
+            created: vec!["/repo/utils.ts".into()],
+            always: false,
+        };
+        let changed =
+            |files: &[&str]| -> BTreeSet<PathBuf> { files.iter().map(PathBuf::from).collect() };
+        assert!(dependencies.is_affected_by(&changed(&["/repo/package.json"])));
+        assert!(dependencies.is_affected_by(&changed(&["/repo/index.ts", "/repo/utils.ts"])));
+        assert!(!dependencies.is_affected_by(&changed(&["/repo/index.ts"])));
+        assert!(!dependencies.is_affected_by(&BTreeSet::new()));
+
+        let always = Dependencies {
+            always: true,
+            ..Default::default()
+        };
+        assert!(always.is_affected_by(&BTreeSet::new()));
+    }
+
+    #[test]
+    fn dependents() {
+        let dir = TempDir::new("incremental");
+        dir.write("package.json", "{}");
+        dir.write(
+            "index.ts",
+            "import { a } from './utils';\nconsole.log(a);\n",
+        );
+        dir.write(
+            "utils/index.ts",
+            "export const a = 1;\nexport const b = 2;\n",
+        );
+        let files = || {
+            let mut files = Vec::new();
+            for file in ["index.ts", "utils.ts", "utils/index.ts"] {
+                if dir.join(file).is_file() {
+                    files.push(dir.join(file));
+                }
+            }
+            files
+        };
+        let fresh = || {
+            let resolver = dir.resolver("");
+            let mut analyzer = Analyzer::new(Default::default());
+            for file in files() {
+                analyzer.add_file(&resolver, &file);
+            }
+            analyzer.finalize()
+        };
+
+        // Kept across changes, like the resolver's cache
+        let resolver = dir.resolver("");
+        let mut analyzer = Analyzer::new(Default::default());
+        for file in files() {
+            analyzer.add_file(&resolver, &file);
+        }
+        assert_eq!(analyzer.finalize(), fresh());
+        let update = |analyzer: &mut Analyzer, changed: &[&str]| {
+            let changed: BTreeSet<PathBuf> = changed.iter().map(|file| dir.join(file)).collect();
+            for file in &changed {
+                resolver.cache.invalidate(file);
+            }
+            let dependents = analyzer.dependents(&changed);
+            for file in changed.iter().chain(&dependents) {
+                analyzer.remove_file(file);
+                if file.is_file() {
+                    analyzer.add_file(&resolver, file);
+                }
+            }
+            dependents
+        };
+
+        // `./utils` resolves to the new file instead.
+        dir.write("utils.ts", "export const a = 1;\n");
+        assert_eq!(update(&mut analyzer, &["utils.ts"]), [dir.join("index.ts")]);
+        let created = analyzer.finalize();
+        assert!(created.unused_files.contains(&dir.join("utils/index.ts")));
+        assert_eq!(created, fresh());
+
+        dir.write("utils/index.ts", "export const a = 1;\n");
+        assert!(update(&mut analyzer, &["utils/index.ts"]).is_empty());
+        assert_eq!(analyzer.finalize(), fresh());
+
+        dir.remove("utils.ts");
+        assert_eq!(update(&mut analyzer, &["utils.ts"]), [dir.join("index.ts")]);
+        assert_eq!(analyzer.finalize(), fresh());
+    }
+}
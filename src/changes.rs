@@ -0,0 +1,234 @@
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use crate::baseline::{Baseline, BaselineEntry};
+use crate::AnalysisResults;
+
+// The files of a git repository that changed since a revision, including uncommitted and
+// untracked ones, for reporting only the findings a change is responsible for.
+#[derive(Debug, Default)]
+pub struct Changes {
+    // Files that were added or modified, absolute
+    pub changed: HashSet<PathBuf>,
+    // file -> its contents at the revision, for files that were modified or deleted
+    pub old_contents: HashMap<PathBuf, String>,
+}
+
+impl Changes {
+    // Reads the changes to the files in `root`, which has to be in a git work tree, since `rev`.
+    pub fn since(root: &Path, rev: &str) -> Result<Self, String> {
+        git(
+            root,
+            &[
+                "rev-parse",
+                "--verify",
+                "--quiet",
+                &format!("{}^{{commit}}", rev),
+            ],
+        )
+        .map_err(|_| format!("unknown revision {:?}", rev))?;
+
+        let mut changes = Self::default();
+        // `--relative` limits the diff to `root` and makes the paths relative to it.
+        let diff = git(
+            root,
+            &[
+                "diff",
+                "--name-status",
+                "--no-renames",
+                "--relative",
+                "-z",
+                rev,
+                "--",
+            ],
+        )?;
+        let mut fields = diff.split('\0').filter(|field| !field.is_empty());
+        while let (Some(status), Some(file)) = (fields.next(), fields.next()) {
+            let path = root.join(file);
+            if !status.starts_with('A') {
+                // `./` makes the path relative to the working directory rather than the top of
+                // the work tree.
+                let old = git(root, &["show", &format!("{}:./{}", rev, file)])?;
+                changes.old_contents.insert(path.clone(), old);
+            }
+            if !status.starts_with('D') {
+                changes.changed.insert(path);
+            }
+        }
+
+        let untracked = git(root, &["ls-files", "--others", "--exclude-standard", "-z"])?;
+        for file in untracked.split('\0').filter(|file| !file.is_empty()) {
+            changes.changed.insert(root.join(file));
+        }
+        Ok(changes)
+    }
+
+    // Keeps the findings the change is responsible for: those in changed files, and those that
+    // `old`, the results with the files as they were at the revision, doesn't have, e.g. an
+    // export whose last importer was removed.
+    pub fn retain_attributable(
+        &self,
+        results: &mut AnalysisResults,
+        old: &AnalysisResults,
+        root: &Path,
+    ) {
+        let old: HashSet<BaselineEntry> = Baseline::new(old, root).entries.into_iter().collect();
+        results.retain(|file, symbol, kind| {
+            self.changed.contains(file)
+                || !old.contains(&BaselineEntry::new(root, file, symbol, kind))
+        });
+    }
+}
+
+fn git(dir: &Path, args: &[&str]) -> Result<String, String> {
+    let output = Command::new("git")
+        .args(args)
+        .current_dir(dir)
+        .output()
+        .map_err(|err| format!("failed to run git: {}", err))?;
+    if !output.status.success() {
+        return Err(format!(
+            "git {} failed: {}",
+            args.join(" "),
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixture::TempDir;
+    use crate::Analyzer;
+
+    fn commit(dir: &TempDir) {
+        git(dir.path(), &["add", "."]).unwrap();
+        let user = ["-c", "user.name=test", "-c", "user.email=test@example.com"];
+        git(
+            dir.path(),
+            &[&user[..], &["commit", "-q", "-m", "test"]].concat(),
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn since() {
+        let dir = TempDir::new("since");
+        git(dir.path(), &["init", "-q"]).unwrap();
+        dir.write(".gitignore", "dist/\n");
+        for file in ["a.ts", "b.ts", "web/c.ts", "web/d.ts"] {
+            dir.write(file, file);
+        }
+        commit(&dir);
+        dir.write("a.ts", "committed");
+        commit(&dir);
+
+        dir.remove("b.ts");
+        dir.write("web/c.ts", "modified");
+        dir.remove("web/d.ts");
+        dir.write("e.ts", "");
+        git(dir.path(), &["add", "e.ts"]).unwrap();
+        dir.write("f.ts", "");
+        dir.write("web/g.ts", "");
+        dir.write("dist/index.js", "");
+
+        let changes = Changes::since(dir.path(), "HEAD~1").unwrap();
+        assert_eq!(
+            changes.changed,
+            ["a.ts", "e.ts", "f.ts", "web/c.ts", "web/g.ts"]
+                .map(|file| dir.join(file))
+                .into()
+        );
+        assert_eq!(
+            changes.old_contents,
+            ["a.ts", "b.ts", "web/c.ts", "web/d.ts"]
+                .map(|file| (dir.join(file), file.to_owned()))
+                .into()
+        );
+
+        // Only the changes in a subdirectory of the repo
+        let web = dir.join("web");
+        let changes = Changes::since(&web, "HEAD").unwrap();
+        assert_eq!(changes.changed, [web.join("c.ts"), web.join("g.ts")].into());
+        assert_eq!(
+            changes.old_contents,
+            [
+                (web.join("c.ts"), "web/c.ts".to_owned()),
+                (web.join("d.ts"), "web/d.ts".to_owned()),
+            ]
+            .into()
+        );
+
+        assert_eq!(
+            Changes::since(dir.path(), "no-such-rev").unwrap_err(),
+            "unknown revision \"no-such-rev\""
+        );
+    }
+
+    #[test]
+    fn attributable_findings() {
+        let dir = TempDir::new("attributable");
+        dir.write("package.json", "{}");
+        dir.write("a.ts", "console.log(2);\n");
+        dir.write(
+            "b.ts",
+            "export const w = 1;\nexport const x = 2;\nexport const y = 3;\n",
+        );
+        dir.write("c.ts", "export const z = 4;\n");
+        // `w` and `x` lose their importers, and c.ts is new. `y` was unused before.
+        let changes = Changes {
+            changed: HashSet::from([dir.join("a.ts"), dir.join("c.ts")]),
+            old_contents: HashMap::from([
+                (
+                    dir.join("a.ts"),
+                    "import { x } from './b';\nconsole.log(x);\n".into(),
+                ),
+                (
+                    dir.join("old.ts"),
+                    "import { w } from './b';\nconsole.log(w);\n".into(),
+                ),
+            ]),
+        };
+
+        let analyze = |overlays: &HashMap<PathBuf, String>| {
+            let resolver = dir.resolver("");
+            let mut analyzer = Analyzer::new(Default::default());
+            let mut files = vec![dir.join("a.ts"), dir.join("b.ts"), dir.join("c.ts")];
+            for (file, source) in overlays {
+                analyzer.add_overlay(file, source.clone());
+                if !files.contains(file) {
+                    files.push(file.clone());
+                }
+            }
+            for file in files {
+                analyzer.add_file(&resolver, &file);
+            }
+            analyzer.finalize()
+        };
+        let old = analyze(&changes.old_contents);
+        let mut results = analyze(&HashMap::new());
+        changes.retain_attributable(&mut results, &old, dir.path());
+        let unused: HashMap<&PathBuf, Vec<String>> = results
+            .modules
+            .iter()
+            .map(|(file, module_results)| {
+                let mut names: Vec<String> = module_results
+                    .unused_exports
+                    .iter()
+                    .map(|export| export.original_name.to_string())
+                    .collect();
+                names.sort();
+                (file, names)
+            })
+            .collect();
+        assert_eq!(
+            unused,
+            HashMap::from([
+                (&dir.join("b.ts"), vec!["w".to_owned(), "x".to_owned()]),
+                (&dir.join("c.ts"), vec!["z".to_owned()]),
+            ])
+        );
+    }
+}
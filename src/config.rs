@@ -0,0 +1,366 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde::Deserialize;
+use serde_json::Value;
+
+use crate::files::matches_any;
+use crate::report::FindingKind;
+use crate::AnalysisResults;
+
+pub const CONFIG_FILE: &str = "ts-deadcode.json";
+// The key of a package.json that holds the config if there's no `CONFIG_FILE`
+pub const PACKAGE_JSON_KEY: &str = "ts-deadcode";
+
+// The settings of the repo root or a workspace package. Globs are relative to the directory of
+// the config, and match like `--include`'s.
+#[derive(Clone, Debug, Default, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "camelCase")]
+pub struct Config {
+    // Files used from outside the repo, like `--entry`
+    pub entry: Vec<String>,
+    // Files not to analyze, like `--exclude`
+    pub ignore: Vec<String>,
+    // Test files, like `--test`. Replaces the globs of enclosing configs.
+    pub test: Option<Vec<String>>,
+    // Like `--custom-condition`. Imports are resolved the same way everywhere, so these apply to
+    // the whole repo.
+    pub custom_conditions: Vec<String>,
+    // e.g. `{ "unused-file": "error" }`
+    pub rules: HashMap<FindingKind, RuleSeverity>,
+    // Rules for the files matching some of their globs, applied after `rules` in order
+    pub overrides: Vec<Override>,
+    // Names of exports that are never reported, e.g. `default` or `use*`
+    pub allow_exports: Vec<String>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Override {
+    pub files: Vec<String>,
+    #[serde(default)]
+    pub rules: HashMap<FindingKind, RuleSeverity>,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RuleSeverity {
+    // Not reported
+    Off,
+    #[default]
+    Warn,
+    // Reported, and fails the run
+    Error,
+}
+
+impl Config {
+    // Reads the config of the package or repo in `dir`, if it has one.
+    pub fn read(dir: &Path) -> Result<Option<Self>, String> {
+        let path = dir.join(CONFIG_FILE);
+        if let Ok(mut contents) = fs::read_to_string(&path) {
+            let _ = json_comments::strip_comments_in_place(&mut contents, Default::default(), true);
+            return serde_json::from_str(&contents)
+                .map(Some)
+                .map_err(|err| format!("{}: invalid config: {}", path.display(), err));
+        }
+
+        let path = dir.join("package.json");
+        let Ok(contents) = fs::read_to_string(&path) else {
+            return Ok(None);
+        };
+        // Invalid package.jsons are reported where they're used.
+        let Ok(mut package) = serde_json::from_str::<Value>(&contents) else {
+            return Ok(None);
+        };
+        match package.get_mut(PACKAGE_JSON_KEY).map(Value::take) {
+            Some(config) => serde_json::from_value(config).map(Some).map_err(|err| {
+                format!(
+                    "{}: invalid {:?}: {}",
+                    path.display(),
+                    PACKAGE_JSON_KEY,
+                    err
+                )
+            }),
+            None => Ok(None),
+        }
+    }
+}
+
+// The configs of a repo. Each file gets the settings of the configs of the directories it's in,
+// those of nested directories overriding the others.
+#[derive(Clone, Debug, Default)]
+pub struct Configs {
+    // Sorted, so enclosing directories come first
+    configs: Vec<(PathBuf, Config)>,
+}
+
+impl Configs {
+    // Reads the configs in `dirs`, e.g. the repo root and its packages.
+    pub fn read(dirs: &[PathBuf]) -> Result<Self, String> {
+        let mut configs = Vec::new();
+        for dir in dirs {
+            if let Some(config) = Config::read(dir)? {
+                configs.push((dir.clone(), config));
+            }
+        }
+        configs.sort_by(|a, b| a.0.cmp(&b.0));
+        configs.dedup_by(|a, b| a.0 == b.0);
+        Ok(Self { configs })
+    }
+
+    // The configs that apply to `file` with its path relative to them, outermost first.
+    fn applying<'a>(&'a self, file: &'a Path) -> impl Iterator<Item = (&'a Path, &'a Config)> {
+        self.configs.iter().filter_map(move |(dir, config)| {
+            let relative_path = file.strip_prefix(dir).ok()?;
+            Some((relative_path, config))
+        })
+    }
+
+    pub fn is_entry_point(&self, file: &Path) -> bool {
+        self.applying(file)
+            .any(|(relative_path, config)| matches_any(&config.entry, relative_path))
+    }
+
+    pub fn is_ignored(&self, file: &Path) -> bool {
+        self.applying(file)
+            .any(|(relative_path, config)| matches_any(&config.ignore, relative_path))
+    }
+
+    // Whether `file` is a test, or `None` if none of its configs has test globs.
+    pub fn is_test_file(&self, file: &Path) -> Option<bool> {
+        self.applying(file)
+            .filter_map(|(relative_path, config)| {
+                let test = config.test.as_ref()?;
+                Some(matches_any(test, relative_path))
+            })
+            .last()
+    }
+
+    pub fn custom_conditions(&self) -> impl Iterator<Item = &String> {
+        self.configs
+            .iter()
+            .flat_map(|(_, config)| &config.custom_conditions)
+    }
+
+    pub fn severity(&self, file: &Path, kind: FindingKind) -> RuleSeverity {
+        let mut severity = RuleSeverity::default();
+        for (relative_path, config) in self.applying(file) {
+            let overrides = config
+                .overrides
+                .iter()
+                .filter(|rules| matches_any(&rules.files, relative_path));
+            let rules = [&config.rules]
+                .into_iter()
+                .chain(overrides.map(|rules| &rules.rules));
+            for rules in rules {
+                if let Some(rule_severity) = rules.get(&kind) {
+                    severity = *rule_severity;
+                }
+            }
+        }
+        severity
+    }
+
+    pub fn is_allowed_export(&self, file: &Path, name: &str) -> bool {
+        self.applying(file).any(|(_, config)| {
+            config
+                .allow_exports
+                .iter()
+                .any(|pattern| pattern == name || glob_match::glob_match(pattern, name))
+        })
+    }
+
+    // Drops the findings whose rule is off, and the allowed exports.
+    pub fn apply(&self, results: &mut AnalysisResults) {
+        results.retain(|file, symbol, kind| {
+            let export = matches!(
+                kind,
+                FindingKind::UnusedExport
+                    | FindingKind::UnusedTypeExport
+                    | FindingKind::TestOnlyExport
+            );
+            self.severity(file, kind) != RuleSeverity::Off
+                && !(export && self.is_allowed_export(file, symbol))
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixture::TempDir;
+    use crate::Analyzer;
+
+    #[test]
+    fn read() {
+        let dir = TempDir::new("config");
+        assert_eq!(Config::read(dir.path()), Ok(None));
+        dir.write("package.json", "{");
+        assert_eq!(Config::read(dir.path()), Ok(None));
+        dir.write("package.json", r#"{ "name": "a" }"#);
+        assert_eq!(Config::read(dir.path()), Ok(None));
+        dir.write(
+            "package.json",
+            r#"{ "ts-deadcode": { "entry": ["bin/*.ts"] } }"#,
+        );
+        assert_eq!(
+            Config::read(dir.path()),
+            Ok(Some(Config {
+                entry: vec!["bin/*.ts".into()],
+                ..Default::default()
+            }))
+        );
+        dir.write("package.json", r#"{ "ts-deadcode": { "entries": [] } }"#);
+        assert!(Config::read(dir.path()).is_err());
+
+        // The config file is used instead of the package.json.
+        dir.write(
+            CONFIG_FILE,
+            r#"{
+                // Comments are allowed.
+                "rules": { "unused-file": "error" },
+            }"#,
+        );
+        assert_eq!(
+            Config::read(dir.path()),
+            Ok(Some(Config {
+                rules: HashMap::from([(FindingKind::UnusedFile, RuleSeverity::Error)]),
+                ..Default::default()
+            }))
+        );
+        dir.write(CONFIG_FILE, r#"{ "rules": { "unused-thing": "off" } }"#);
+        assert!(Config::read(dir.path()).is_err());
+        dir.write(CONFIG_FILE, r#"{ "rules": { "unused-file": "fatal" } }"#);
+        assert!(Config::read(dir.path()).is_err());
+    }
+
+    #[test]
+    fn nested_configs() {
+        let dir = TempDir::new("configs");
+        dir.write(
+            CONFIG_FILE,
+            r#"{
+                "entry": ["bin/*.ts"],
+                "ignore": ["generated"],
+                "test": ["**/*.test.ts"],
+                "allowExports": ["default", "use*"],
+            }"#,
+        );
+        dir.write(
+            "packages/a/package.json",
+            r#"{ "ts-deadcode": { "test": ["*.check.ts"] } }"#,
+        );
+        dir.write("packages/b/package.json", r#"{ "ts-deadcode": {} }"#);
+        let dirs = ["packages/b", "", "packages/a"].map(|package| dir.join(package));
+        let configs = Configs::read(&dirs).unwrap();
+
+        // Globs are relative to their config.
+        assert!(configs.is_entry_point(&dir.join("bin/cli.ts")));
+        assert!(!configs.is_entry_point(&dir.join("src/cli.ts")));
+        assert!(!configs.is_entry_point(&dir.join("packages/a/bin/cli.ts")));
+        assert!(configs.is_ignored(&dir.join("packages/a/generated/a.ts")));
+        assert!(!configs.is_ignored(&dir.join("packages/a/src/a.ts")));
+
+        // Test globs replace those of enclosing configs, if there are any.
+        let is_test_file = |file: &str| configs.is_test_file(&dir.join(file));
+        assert_eq!(is_test_file("src/a.test.ts"), Some(true));
+        assert_eq!(is_test_file("src/a.check.ts"), Some(false));
+        assert_eq!(is_test_file("packages/a/src/a.test.ts"), Some(false));
+        assert_eq!(is_test_file("packages/a/src/a.check.ts"), Some(true));
+        assert_eq!(is_test_file("packages/b/src/a.test.ts"), Some(true));
+        assert_eq!(
+            Configs::default().is_test_file(&dir.join("a.test.ts")),
+            None
+        );
+
+        assert!(configs.is_allowed_export(&dir.join("packages/a/src/a.ts"), "useThing"));
+        assert!(configs.is_allowed_export(&dir.join("src/a.ts"), "default"));
+        assert!(!configs.is_allowed_export(&dir.join("src/a.ts"), "thing"));
+    }
+
+    #[test]
+    fn severities() {
+        let dir = TempDir::new("severities");
+        dir.write(
+            CONFIG_FILE,
+            r#"{
+                "rules": { "unused-file": "error" },
+                "overrides": [
+                    { "files": ["generated"], "rules": { "unused-export": "off", "unused-file": "off" } },
+                    { "files": ["keep.ts"], "rules": { "unused-file": "warn" } },
+                ],
+            }"#,
+        );
+        dir.write(
+            "packages/a/ts-deadcode.json",
+            r#"{ "rules": { "unused-export": "error" } }"#,
+        );
+        let configs = Configs::read(&[dir.path().to_owned(), dir.join("packages/a")]).unwrap();
+        let severity = |file: &str, kind| configs.severity(&dir.join(file), kind);
+
+        assert_eq!(
+            severity("src/a.ts", FindingKind::UnusedFile),
+            RuleSeverity::Error
+        );
+        assert_eq!(
+            severity("src/a.ts", FindingKind::UnusedExport),
+            RuleSeverity::Warn
+        );
+        // Overrides apply after the rules, in order.
+        assert_eq!(
+            severity("src/generated/a.ts", FindingKind::UnusedFile),
+            RuleSeverity::Off
+        );
+        assert_eq!(
+            severity("src/generated/keep.ts", FindingKind::UnusedFile),
+            RuleSeverity::Warn
+        );
+        // Nested configs apply after the overrides of enclosing ones.
+        assert_eq!(
+            severity("packages/a/generated/a.ts", FindingKind::UnusedExport),
+            RuleSeverity::Error
+        );
+        assert_eq!(
+            severity("packages/a/generated/a.ts", FindingKind::UnusedFile),
+            RuleSeverity::Off
+        );
+    }
+
+    #[test]
+    fn apply() {
+        let dir = TempDir::new("config-apply");
+        dir.write(
+            CONFIG_FILE,
+            r#"{
+                "overrides": [{ "files": ["generated"], "rules": { "unused-export": "off" } }],
+                "allowExports": ["use*"],
+            }"#,
+        );
+        dir.write("package.json", "{}");
+        dir.write(
+            "src/a.ts",
+            "import 'useless';\nexport const useThing = 1;\nexport const thing = 2;\n",
+        );
+        dir.write("src/generated/b.ts", "export const b = 1;\n");
+
+        let resolver = dir.resolver("");
+        let mut analyzer = Analyzer::new(Default::default());
+        for file in ["src/a.ts", "src/generated/b.ts"] {
+            analyzer.add_file(&resolver, &dir.join(file));
+        }
+        let mut results = analyzer.finalize();
+        Configs::read(&[dir.path().to_owned()])
+            .unwrap()
+            .apply(&mut results);
+
+        let a = &results.modules[&dir.join("src/a.ts")];
+        assert_eq!(a.unused_exports.len(), 1);
+        assert_eq!(&*a.unused_exports[0].exported_name, "thing");
+        assert!(!results
+            .modules
+            .contains_key(&dir.join("src/generated/b.ts")));
+        // Only exports are allowed by name.
+        assert_eq!(results.unresolved_imports.len(), 1);
+    }
+}
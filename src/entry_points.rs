@@ -0,0 +1,114 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde_json::Value;
+
+// package.json fields that point at files loaded from outside the package.
+const ENTRY_POINT_FIELDS: [&str; 6] = ["main", "module", "source", "browser", "bin", "exports"];
+
+// Extensions to try when a target doesn't exist as written, e.g. `"main": "index.js"` for a
+// package whose source is `index.ts`.
+const SOURCE_EXTENSIONS: [&str; 8] = ["ts", "tsx", "mts", "cts", "js", "jsx", "mjs", "cjs"];
+
+// Build output directories whose sources usually live in `src`, e.g. `dist/index.js`.
+const OUTPUT_DIRS: [&str; 4] = ["dist", "lib", "build", "out"];
+
+// Returns the source files referenced by the `main`, `module`, `source`, `browser`, `bin` and
+// `exports` fields of the package.json in `package_dir`. Targets that don't map to a file on
+// disk are skipped.
+pub fn package_entry_points(package_dir: &Path) -> Result<Vec<PathBuf>, String> {
+    let contents = match fs::read_to_string(package_dir.join("package.json")) {
+        Ok(contents) => contents,
+        Err(_) => return Ok(Vec::new()),
+    };
+    let package: Value = match serde_json::from_str(&contents) {
+        Ok(package) => package,
+        Err(err) => return Err(format!("invalid package.json: {}", err)),
+    };
+
+    let mut targets = Vec::new();
+    for field in ENTRY_POINT_FIELDS {
+        if let Some(value) = package.get(field) {
+            collect_targets(value, &mut targets);
+        }
+    }
+
+    let mut entry_points: Vec<PathBuf> = targets
+        .into_iter()
+        .filter_map(|target| resolve_target(package_dir, target))
+        .collect();
+    entry_points.sort();
+    entry_points.dedup();
+    Ok(entry_points)
+}
+
+// Collects the paths in a package.json field. `bin`, `browser` and `exports` nest them in
+// objects of commands, replacements, subpaths or conditions, and `exports` may use fallback
+// arrays.
+fn collect_targets<'a>(value: &'a Value, targets: &mut Vec<&'a str>) {
+    match value {
+        Value::String(target) => targets.push(target),
+        Value::Array(values) => {
+            for value in values {
+                collect_targets(value, targets);
+            }
+        }
+        Value::Object(map) => {
+            for value in map.values() {
+                collect_targets(value, targets);
+            }
+        }
+        _ => {}
+    }
+}
+
+fn resolve_target(package_dir: &Path, target: &str) -> Option<PathBuf> {
+    // Subpath patterns like `./*` don't name a single file, and targets outside the package
+    // belong to someone else.
+    if target.contains('*') || target.starts_with("..") {
+        return None;
+    }
+    let relative = Path::new(target.trim_start_matches("./"));
+    if relative.is_absolute() {
+        return None;
+    }
+
+    let mut candidates = vec![package_dir.join(relative)];
+    let mut components = relative.components();
+    if let Some(first) = components.next() {
+        if OUTPUT_DIRS.iter().any(|dir| first.as_os_str() == *dir) {
+            candidates.push(package_dir.join("src").join(components.as_path()));
+        }
+    }
+
+    for candidate in candidates {
+        if let Some(path) = find_source(&candidate) {
+            return fs::canonicalize(path).ok();
+        }
+    }
+    None
+}
+
+// Finds the source for `path`, trying other extensions and `index` files.
+fn find_source(path: &Path) -> Option<PathBuf> {
+    if path.is_file() {
+        let ext = path.extension().unwrap_or_default();
+        return match SOURCE_EXTENSIONS
+            .iter()
+            .any(|source_ext| ext == *source_ext)
+        {
+            true => Some(path.into()),
+            // e.g. `"./package.json": "./package.json"`
+            false => None,
+        };
+    }
+    let stem = if path.is_dir() {
+        path.join("index")
+    } else {
+        path.into()
+    };
+    SOURCE_EXTENSIONS
+        .iter()
+        .map(|ext| stem.with_extension(ext))
+        .find(|candidate| candidate.is_file())
+}
@@ -0,0 +1,238 @@
+use std::path::{Path, PathBuf};
+
+use ignore::WalkBuilder;
+
+use crate::{Diagnostic, Severity};
+
+// Which files in a repo are analyzed: those not ignored by `.gitignore` or `.ignore` files, that
+// match one of the `include` globs if there are any, and none of the `exclude` globs.
+//
+// Globs are relative to the repo root, and also match the files in a matching directory. A glob
+// without `/` matches a file or directory name anywhere, e.g. `__generated__` or
+// `*.stories.tsx`.
+#[derive(Clone, Debug, Default)]
+pub struct FileFilter {
+    pub include: Vec<String>,
+    pub exclude: Vec<String>,
+}
+
+impl FileFilter {
+    // The files in `root` that aren't ignored or excluded, sorted, and warnings about the
+    // directories and ignore files that couldn't be read. `node_modules` and `.git` are always
+    // skipped. Doesn't apply `include`, since files like package.jsons are needed either way.
+    //
+    // Symlinks aren't followed, so no file is found twice: packages symlinked into the repo are
+    // found where they really are, if they're part of the repo at all.
+    pub fn walk(&self, root: &Path) -> (Vec<PathBuf>, Vec<Diagnostic>) {
+        let filter_root = root.to_path_buf();
+        let exclude = self.exclude.clone();
+        let walk = WalkBuilder::new(root)
+            // Dotfiles like `.storybook/main.ts` may be code too.
+            .hidden(false)
+            // Apply ignore files even outside a git repo, but not the user's global ones, so the
+            // results are the same on every machine.
+            .require_git(false)
+            .git_global(false)
+            .sort_by_file_name(|a, b| a.cmp(b))
+            .filter_entry(move |entry| {
+                if entry.file_name() == "node_modules" || entry.file_name() == ".git" {
+                    return false;
+                }
+                let relative_path = entry
+                    .path()
+                    .strip_prefix(&filter_root)
+                    .unwrap_or(entry.path());
+                !matches_any(&exclude, relative_path)
+            })
+            .build();
+
+        let mut files = Vec::new();
+        let mut diagnostics = Vec::new();
+        let mut warn = |err: &ignore::Error| {
+            diagnostics.push(Diagnostic {
+                severity: Severity::Warning,
+                file: error_path(err).unwrap_or(root).into(),
+                message: format!("failed to find files: {}", err),
+            })
+        };
+        for entry in walk {
+            let entry = match entry {
+                Ok(entry) => entry,
+                Err(err) => {
+                    warn(&err);
+                    continue;
+                }
+            };
+            // Errors reading a directory's ignore files come with the directory.
+            if let Some(err) = entry.error() {
+                warn(err);
+            }
+            if entry
+                .file_type()
+                .is_some_and(|file_type| file_type.is_file())
+            {
+                files.push(entry.into_path());
+            }
+        }
+        (files, diagnostics)
+    }
+
+    // Whether the file at `relative_path`, relative to the repo root, is analyzed. Files that
+    // aren't found by `walk`, e.g. overlays, are checked against `exclude` too.
+    pub fn is_included(&self, relative_path: &Path) -> bool {
+        (self.include.is_empty() || matches_any(&self.include, relative_path))
+            && !matches_any(&self.exclude, relative_path)
+    }
+}
+
+fn error_path(err: &ignore::Error) -> Option<&Path> {
+    match err {
+        ignore::Error::WithPath { path, .. } => Some(path),
+        ignore::Error::WithLineNumber { err, .. } | ignore::Error::WithDepth { err, .. } => {
+            error_path(err)
+        }
+        ignore::Error::Partial(errs) => errs.iter().find_map(error_path),
+        _ => None,
+    }
+}
+
+// Whether any of `patterns` matches the file or directory at `relative_path`, as described for
+// `FileFilter`.
+pub fn matches_any(patterns: &[String], relative_path: &Path) -> bool {
+    patterns.iter().any(|pattern| {
+        let pattern = pattern.trim_start_matches("./").trim_end_matches('/');
+        // The file itself or one of the directories it's in
+        relative_path
+            .ancestors()
+            .take_while(|path| !path.as_os_str().is_empty())
+            .any(|path| {
+                glob_matches(pattern, path)
+                    || (!pattern.contains('/')
+                        && path
+                            .file_name()
+                            .is_some_and(|name| glob_matches(pattern, Path::new(name))))
+            })
+    })
+}
+
+fn glob_matches(pattern: &str, path: &Path) -> bool {
+    let path = path.to_string_lossy();
+    pattern == path || glob_match::glob_match(pattern, &path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixture::TempDir;
+
+    #[test]
+    fn walk() {
+        let dir = TempDir::new("files");
+        for (file, contents) in [
+            (".gitignore", "dist/\n*.gen.ts\n!keep.gen.ts\n"),
+            (".ignore", "coverage\n"),
+            (".storybook/main.ts", ""),
+            ("coverage/report.js", ""),
+            ("dist/index.js", ""),
+            ("node_modules/dep/index.js", ""),
+            ("src/__generated__/schema.ts", ""),
+            ("src/index.ts", ""),
+            ("src/types.gen.ts", ""),
+            ("src/keep.gen.ts", ""),
+            ("sub/.gitignore", "out/\n"),
+            ("sub/out/index.js", ""),
+            ("sub/index.ts", ""),
+            ("broken/.ignore", "[z-a]\n"),
+        ] {
+            dir.write(file, contents);
+        }
+
+        let filter = FileFilter {
+            include: Vec::new(),
+            exclude: vec!["__generated__".into()],
+        };
+        let (walked, diagnostics) = filter.walk(dir.path());
+        let walked: Vec<_> = walked
+            .into_iter()
+            .map(|file| {
+                file.strip_prefix(dir.path())
+                    .unwrap()
+                    .to_str()
+                    .unwrap()
+                    .to_owned()
+            })
+            .collect();
+        assert_eq!(
+            walked,
+            [
+                ".gitignore",
+                ".ignore",
+                ".storybook/main.ts",
+                "broken/.ignore",
+                "src/index.ts",
+                "src/keep.gen.ts",
+                "sub/.gitignore",
+                "sub/index.ts",
+            ]
+        );
+        // Ignore files with invalid globs are warned about.
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].severity, Severity::Warning);
+        assert_eq!(diagnostics[0].file, dir.path().join("broken/.ignore"));
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn symlinks() {
+        let dir = TempDir::new("symlinks");
+        dir.write("src/index.ts", "");
+        dir.write("sub/index.ts", "");
+        // Links aren't followed, so files aren't found twice, and loops are fine.
+        std::os::unix::fs::symlink(dir.join("src"), dir.join("linked")).unwrap();
+        std::os::unix::fs::symlink(dir.path(), dir.join("sub/loop")).unwrap();
+
+        let (walked, diagnostics) = FileFilter::default().walk(dir.path());
+        assert_eq!(walked, [dir.join("src/index.ts"), dir.join("sub/index.ts")]);
+        assert!(diagnostics.is_empty());
+    }
+
+    #[test]
+    fn patterns() {
+        let matches = |pattern: &str, relative_path: &str| {
+            matches_any(&[pattern.to_owned()], Path::new(relative_path))
+        };
+        assert!(matches("src/index.ts", "src/index.ts"));
+        assert!(matches("src/*.ts", "src/index.ts"));
+        assert!(!matches("src/*.ts", "src/lib/index.ts"));
+        assert!(matches("src/**/*.ts", "src/lib/index.ts"));
+        // Files in a matching directory
+        assert!(matches("src", "src/lib/index.ts"));
+        assert!(matches("./src/", "src/lib/index.ts"));
+        assert!(!matches("src", "lib/src.ts"));
+        // Names anywhere, unless the glob has a `/`
+        assert!(matches("__generated__", "src/__generated__/schema.ts"));
+        assert!(matches("*.gen.ts", "src/types.gen.ts"));
+        assert!(!matches("src/*.ts", "lib/src/index.ts"));
+        assert!(!matches_any(&[], Path::new("src/index.ts")));
+    }
+
+    #[test]
+    fn included() {
+        let filter = FileFilter {
+            include: vec!["src/**/*.ts".into(), "./sub/".into()],
+            exclude: vec!["*.gen.ts".into()],
+        };
+        assert!(filter.is_included(Path::new("src/index.ts")));
+        assert!(filter.is_included(Path::new("sub/index.ts")));
+        assert!(!filter.is_included(Path::new("src/keep.gen.ts")));
+        assert!(!filter.is_included(Path::new(".storybook/main.ts")));
+
+        // Everything is included unless there are `include` globs.
+        let filter = FileFilter {
+            include: Vec::new(),
+            exclude: vec!["*.gen.ts".into()],
+        };
+        assert!(filter.is_included(Path::new(".storybook/main.ts")));
+        assert!(!filter.is_included(Path::new("src/keep.gen.ts")));
+    }
+}
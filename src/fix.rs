@@ -0,0 +1,318 @@
+use std::collections::HashMap;
+use std::ops::Range;
+
+use swc_atoms::JsWord;
+use swc_common::comments::{Comments, SingleThreadedComments};
+use swc_common::{sync::Lrc, BytePos, FileName, SourceMap, Span, Spanned};
+use swc_ecma_ast::*;
+use swc_ecma_parser::{lexer::Lexer, Parser, StringInput};
+
+use crate::{decl_idents, export_name_atom, is_declarative, pat_idents, syntax, ModuleResults};
+
+// Removes the unused exports in `module_results` from `source`. An export that is still used in
+// the file loses its `export` keyword, anything else is deleted along with its doc comment.
+// Deleting never drops code with side effects, e.g. `export const store = createStore()` only
+// loses its `export` keyword. Untouched code keeps its formatting.
+pub fn fix_source(source: &str, module_results: &ModuleResults) -> Result<String, String> {
+    let cm: Lrc<SourceMap> = Default::default();
+    let fm = cm.new_source_file(FileName::Anon, source.into());
+    let comments = SingleThreadedComments::default();
+    let lexer = Lexer::new(
+        syntax(),
+        Default::default(),
+        StringInput::from(&*fm),
+        Some(&comments),
+    );
+    let module = Parser::new_from(lexer)
+        .parse_module()
+        .map_err(|err| format!("failed to parse: {:?}", err.kind()))?;
+
+    let unused = module_results
+        .unused_exports
+        .iter()
+        .chain(&module_results.unused_type_exports)
+        .map(|export| (export.exported_name.clone(), export.used_in_module))
+        .collect();
+    let mut fixer = Fixer {
+        source,
+        start: fm.start_pos,
+        comments,
+        unused,
+        removals: Vec::new(),
+    };
+    for item in &module.body {
+        if let ModuleItem::ModuleDecl(decl) = item {
+            fixer.fix_module_decl(decl);
+        }
+    }
+    Ok(fixer.apply())
+}
+
+struct Fixer<'a> {
+    source: &'a str,
+    start: BytePos,
+    comments: SingleThreadedComments,
+    // Unused exported name -> whether it's used in the file
+    unused: HashMap<JsWord, bool>,
+    // Byte ranges of `source` to remove
+    removals: Vec<Range<usize>>,
+}
+
+impl<'a> Fixer<'a> {
+    fn fix_module_decl(&mut self, decl: &ModuleDecl) {
+        let default: JsWord = "default".into();
+        match decl {
+            ModuleDecl::ExportDecl(export_decl) => self.fix_export_decl(export_decl),
+            ModuleDecl::ExportDefaultDecl(export_default) => {
+                let has_name = match &export_default.decl {
+                    DefaultDecl::Class(class) => class.ident.is_some(),
+                    DefaultDecl::Fn(func) => func.ident.is_some(),
+                    DefaultDecl::TsInterfaceDecl(_) => true,
+                };
+                match self.unused.get(&default) {
+                    Some(false) => {
+                        let mut span = export_default.span;
+                        if let DefaultDecl::Class(class) = &export_default.decl {
+                            span = with_decorators(span, &class.class.decorators);
+                        }
+                        self.remove_statement(span)
+                    }
+                    // export default function helper() {}
+                    Some(true) if has_name => {
+                        self.remove_keywords(export_default.span, &["export", "default"])
+                    }
+                    _ => {}
+                }
+            }
+            ModuleDecl::ExportDefaultExpr(export_default) => {
+                // export default helper;
+                let is_ident = matches!(*export_default.expr, Expr::Ident(_));
+                let unused_here = self.unused.get(&default) == Some(&false);
+                if self.unused.contains_key(&default)
+                    && (is_ident || (unused_here && is_declarative(&export_default.expr)))
+                {
+                    self.remove_statement(export_default.span);
+                }
+            }
+            ModuleDecl::ExportNamed(named_export) => {
+                let remove: Vec<bool> = named_export
+                    .specifiers
+                    .iter()
+                    .map(|specifier| {
+                        let name = match specifier {
+                            ExportSpecifier::Named(named) => {
+                                export_name_atom(named.exported.as_ref().unwrap_or(&named.orig))
+                            }
+                            ExportSpecifier::Namespace(namespace) => {
+                                export_name_atom(&namespace.name)
+                            }
+                            ExportSpecifier::Default(default) => default.exported.sym.clone(),
+                        };
+                        // The local binding is left alone, so whether it's used doesn't matter.
+                        self.unused.contains_key(&name)
+                    })
+                    .collect();
+                if !remove.is_empty() && remove.iter().all(|remove| *remove) {
+                    self.remove_statement(named_export.span);
+                } else {
+                    let spans: Vec<Span> = named_export
+                        .specifiers
+                        .iter()
+                        .map(|specifier| specifier.span())
+                        .collect();
+                    self.remove_from_list(&spans, &remove);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn fix_export_decl(&mut self, export_decl: &ExportDecl) {
+        let mut names = Vec::new();
+        decl_idents(&export_decl.decl, true, &mut names);
+        if names.is_empty() {
+            return;
+        }
+
+        if names.iter().all(|name| self.unused.contains_key(name)) {
+            let used_in_module = names.iter().any(|name| self.unused[name]);
+            let has_effects = match &export_decl.decl {
+                Decl::Var(var) => var
+                    .decls
+                    .iter()
+                    .any(|decl| decl.init.as_ref().is_some_and(|init| !is_declarative(init))),
+                _ => false,
+            };
+            if used_in_module || has_effects {
+                self.remove_keywords(export_decl.span, &["export"]);
+            } else if let Decl::Class(class) = &export_decl.decl {
+                let span = with_decorators(export_decl.span, &class.class.decorators);
+                self.remove_statement(span);
+            } else {
+                self.remove_statement(export_decl.span);
+            }
+            return;
+        }
+
+        // Some of the names are used elsewhere, so only the unused ones can go, e.g. `B` in
+        // `export const [A, B] = pair`.
+        if let Decl::Var(var) = &export_decl.decl {
+            let mut remove = Vec::new();
+            for decl in &var.decls {
+                let declarative = decl.init.as_ref().is_none_or(|init| is_declarative(init));
+                if declarative && self.is_removable(&decl.name) {
+                    remove.push(true);
+                    continue;
+                }
+                remove.push(false);
+                self.fix_pattern(&decl.name);
+            }
+            let spans: Vec<Span> = var.decls.iter().map(|decl| decl.span).collect();
+            self.remove_from_list(&spans, &remove);
+        }
+    }
+
+    // Removes the unused bindings from a destructuring pattern.
+    fn fix_pattern(&mut self, pat: &Pat) {
+        match pat {
+            Pat::Array(array) => {
+                for elem in array.elems.iter().flatten() {
+                    // Leave a hole so the other elements keep their positions, e.g. `[, B]`.
+                    if !matches!(elem, Pat::Rest(_)) && self.is_removable(elem) {
+                        let range = self.range(elem.span());
+                        self.removals.push(range);
+                    } else {
+                        self.fix_pattern(elem);
+                    }
+                }
+            }
+            Pat::Object(object) => {
+                // Removing a property would add it to the rest, e.g. `{ A, ...rest }`.
+                if object
+                    .props
+                    .iter()
+                    .any(|prop| matches!(prop, ObjectPatProp::Rest(_)))
+                {
+                    return;
+                }
+                let mut remove = Vec::new();
+                for prop in &object.props {
+                    let value = match prop {
+                        ObjectPatProp::KeyValue(kv) => Some(&*kv.value),
+                        _ => None,
+                    };
+                    let removable = match prop {
+                        ObjectPatProp::Assign(assign) => self.is_unused(&assign.key.sym),
+                        _ => value.is_some_and(|value| self.is_removable(value)),
+                    };
+                    remove.push(removable);
+                    if let (false, Some(value)) = (removable, value) {
+                        self.fix_pattern(value);
+                    }
+                }
+                let spans: Vec<Span> = object.props.iter().map(|prop| prop.span()).collect();
+                self.remove_from_list(&spans, &remove);
+            }
+            Pat::Assign(assign) => self.fix_pattern(&assign.left),
+            _ => {}
+        }
+    }
+
+    // Whether every name bound by `pat` is unused, including in the file.
+    fn is_removable(&self, pat: &Pat) -> bool {
+        let mut names = Vec::new();
+        pat_idents(pat, &mut names);
+        !names.is_empty() && names.iter().all(|name| self.is_unused(name))
+    }
+
+    fn is_unused(&self, name: &JsWord) -> bool {
+        self.unused.get(name) == Some(&false)
+    }
+
+    // Removes the items of a comma-separated list, along with the commas between them. Each
+    // item takes the separator after it, except trailing ones, which take the one before them.
+    fn remove_from_list(&mut self, spans: &[Span], remove: &[bool]) {
+        if remove.iter().all(|remove| *remove) {
+            return;
+        }
+        for (i, span) in spans.iter().enumerate() {
+            if !remove[i] {
+                continue;
+            }
+            let range = if remove[i + 1..].iter().any(|remove| !*remove) {
+                self.range(*span).start..self.range(spans[i + 1]).start
+            } else {
+                self.range(spans[i - 1]).end..self.range(*span).end
+            };
+            self.removals.push(range);
+        }
+    }
+
+    // Removes the given leading keywords of a statement and the whitespace after them, e.g.
+    // `export default ` from `export default function helper() {}`.
+    fn remove_keywords(&mut self, span: Span, keywords: &[&str]) {
+        let start = self.range(span).start;
+        let mut end = start;
+        for keyword in keywords {
+            let rest = &self.source[end..];
+            if !rest.starts_with(keyword) {
+                return;
+            }
+            let after = &rest[keyword.len()..];
+            end += rest.len() - after.trim_start().len();
+        }
+        self.removals.push(start..end);
+    }
+
+    // Removes a whole statement, including its doc comment and the rest of its last line.
+    fn remove_statement(&mut self, span: Span) {
+        let mut range = self.range(span);
+        if let Some(comments) = self.comments.get_leading(span.lo) {
+            for comment in comments
+                .iter()
+                .filter(|comment| comment.text.starts_with('*'))
+            {
+                range.start = range.start.min(self.range(comment.span).start);
+            }
+        }
+
+        let line_start = self.source[..range.start].trim_end_matches([' ', '\t']);
+        if line_start.is_empty() || line_start.ends_with('\n') {
+            range.start = line_start.len();
+        }
+        let rest = &self.source[range.end..];
+        let trailing = rest.trim_start_matches([' ', '\t']);
+        if let Some(next_line) = trailing
+            .strip_prefix("\r\n")
+            .or(trailing.strip_prefix('\n'))
+        {
+            range.end += rest.len() - next_line.len();
+        }
+        self.removals.push(range);
+    }
+
+    fn range(&self, span: Span) -> Range<usize> {
+        (span.lo - self.start).0 as usize..(span.hi - self.start).0 as usize
+    }
+
+    fn apply(mut self) -> String {
+        self.removals.sort_by_key(|range| (range.start, range.end));
+        let mut fixed = String::with_capacity(self.source.len());
+        let mut copied = 0;
+        for range in &self.removals {
+            if range.start > copied {
+                fixed.push_str(&self.source[copied..range.start]);
+            }
+            copied = copied.max(range.end);
+        }
+        fixed.push_str(&self.source[copied..]);
+        fixed
+    }
+}
+
+// Decorators may come before `export`, e.g. `@Component() export class Button {}`.
+fn with_decorators(span: Span, decorators: &[Decorator]) -> Span {
+    decorators.iter().fold(span, |span, decorator| {
+        span.with_lo(span.lo.min(decorator.span.lo))
+    })
+}
@@ -0,0 +1,58 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use parcel_resolver::{Cache, CacheCow, OsFileSystem, Resolver};
+
+// A directory of files for a test. It's removed when dropped, so also when the test fails.
+pub struct TempDir {
+    path: PathBuf,
+}
+
+impl TempDir {
+    // `name` keeps the directories of tests that run at the same time apart.
+    pub fn new(name: &str) -> Self {
+        let path =
+            std::env::temp_dir().join(format!("ts-deadcode-{}-{}", name, std::process::id()));
+        // Left over from a run that was killed
+        let _ = fs::remove_dir_all(&path);
+        fs::create_dir_all(&path).unwrap();
+        // Analyzed paths are canonical, e.g. under /private/var on macOS.
+        let path = fs::canonicalize(path).unwrap();
+        Self { path }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn join(&self, path: impl AsRef<Path>) -> PathBuf {
+        self.path.join(path)
+    }
+
+    // Writes `contents` to `file`, creating the directories it's in.
+    pub fn write(&self, file: impl AsRef<Path>, contents: &str) {
+        let path = self.join(file);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, contents).unwrap();
+    }
+
+    pub fn remove(&self, file: impl AsRef<Path>) {
+        fs::remove_file(self.join(file)).unwrap();
+    }
+
+    // A resolver with a cache of its own for the package in `package`, relative to the
+    // directory, or for the directory itself if it's empty.
+    pub fn resolver(&self, package: &str) -> Resolver<'static, OsFileSystem> {
+        let root = match package {
+            "" => self.path.clone(),
+            package => self.join(package),
+        };
+        Resolver::parcel(root.into(), CacheCow::Owned(Cache::new(OsFileSystem)))
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.path);
+    }
+}
@@ -1,31 +1,70 @@
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::fs;
+use std::hash::Hash;
+use std::io;
 
 use std::path::{Path, PathBuf};
 
-use parcel_resolver::{FileSystem, Resolution, ResolveOptions, Resolver, SpecifierType};
+use cache::FactsCache;
+use parcel_resolver::{
+    FileSystem, Invalidations, Resolution, ResolveOptions, Resolver, SpecifierType,
+};
+use rayon::prelude::*;
+use report::FindingKind;
+use serde::{Deserialize, Serialize};
 use swc_atoms::JsWord;
 use swc_common::{
+    comments::{Comment, CommentKind, SingleThreadedComments},
     errors::{ColorConfig, Handler},
     sync::Lrc,
-    SourceMap,
+    BytePos, FileName, SourceMap, Span, Spanned,
 };
 use swc_ecma_ast::*;
 use swc_ecma_parser::{lexer::Lexer, Parser, StringInput, Syntax, TsConfig};
 use swc_ecma_visit::Visit;
 use swc_ecma_visit::VisitWith;
 
-#[derive(Debug)]
+pub mod baseline;
+pub mod cache;
+pub mod changes;
+pub mod config;
+pub mod entry_points;
+pub mod files;
+pub mod fix;
+#[cfg(test)]
+mod fixture;
+pub mod lsp;
+pub mod report;
+pub mod sarif;
+pub mod watch;
+pub mod workspace;
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
 pub struct ImportUsage {
     // Filename -> symbols
     imports: HashMap<PathBuf, HashSet<JsWord>>,
+    // Filename -> symbols bound by `import { symbol }`. How the binding is used is recorded in
+    // `imports` and `member_imports`.
+    named_imports: HashMap<PathBuf, HashSet<JsWord>>,
+    // Filename -> symbol -> members accessed on it, e.g. `Namespace.Class`
+    member_imports: HashMap<PathBuf, HashMap<JsWord, HashSet<JsWord>>>,
+    // Files whose module object is used in ways we can't follow, e.g. `console.log(utils)`
+    escaped_modules: HashSet<PathBuf>,
+    // Files that are loaded, e.g. `import './polyfill'` or `export * from './utils'`
+    dependencies: HashSet<PathBuf>,
 }
 
-impl ImportUsage {
-    fn new() -> Self {
-        Self {
-            imports: HashMap::new(),
-        }
-    }
+// The top-level declaration that code belongs to, keyed by its first bound name. `None` is
+// module-level code, which runs whenever the module is loaded.
+type Owner = Option<JsWord>;
+
+// Every file is parsed as TypeScript with JSX and decorators, which also covers JavaScript.
+fn syntax() -> Syntax {
+    Syntax::Typescript(TsConfig {
+        tsx: true,
+        decorators: true,
+        ..Default::default()
+    })
 }
 
 fn export_name_atom(export: &ModuleExportName) -> JsWord {
@@ -35,16 +74,241 @@ fn export_name_atom(export: &ModuleExportName) -> JsWord {
     }
 }
 
+// Returns the property name of `obj.prop` or `obj['prop']`.
+fn member_prop_atom(prop: &MemberProp) -> Option<JsWord> {
+    match prop {
+        MemberProp::Ident(ident) => Some(ident.sym.clone()),
+        MemberProp::Computed(computed) => match *computed.expr {
+            Expr::Lit(Lit::Str(ref str)) => Some(str.value.clone()),
+            _ => None,
+        },
+        MemberProp::PrivateName(_) => None,
+    }
+}
+
+// What a local name refers to, as far as import tracking is concerned.
+#[derive(Clone, Debug)]
+enum Binding {
+    // Any binding we don't track, e.g. `const utils = {}`. These still shadow outer bindings.
+    Local,
+    // A module object, e.g. `import * as utils from 'file'` or `const utils = require('file')`.
+    Module(JsWord),
+    // A named or default import, e.g. `import { Namespace } from 'file'`. Member accesses on
+    // these matter when the import turns out to be a namespace re-export.
+    Import(JsWord, JsWord),
+}
+
+type Scope = HashMap<JsWord, Binding>;
+
+// Whether evaluating `expr` has no effects beyond creating a value, so the code in it only
+// matters if the value is used, e.g. `const handler = () => api.fetch()`.
+fn is_declarative(expr: &Expr) -> bool {
+    match expr {
+        Expr::Ident(_) | Expr::Lit(_) | Expr::Fn(_) | Expr::Arrow(_) => true,
+        Expr::Class(class) => class.class.decorators.is_empty(),
+        Expr::Tpl(tpl) => tpl.exprs.iter().all(|expr| is_declarative(expr)),
+        Expr::Array(array) => array
+            .elems
+            .iter()
+            .flatten()
+            .all(|elem| elem.spread.is_none() && is_declarative(&elem.expr)),
+        Expr::Object(object) => object.props.iter().all(|prop| match prop {
+            PropOrSpread::Prop(prop) => match &**prop {
+                Prop::KeyValue(kv) => {
+                    !matches!(kv.key, PropName::Computed(_)) && is_declarative(&kv.value)
+                }
+                Prop::Assign(_) => false,
+                _ => true,
+            },
+            PropOrSpread::Spread(_) => false,
+        }),
+        Expr::Paren(paren) => is_declarative(&paren.expr),
+        Expr::TsAs(as_expr) => is_declarative(&as_expr.expr),
+        Expr::TsConstAssertion(assertion) => is_declarative(&assertion.expr),
+        Expr::TsSatisfies(satisfies) => is_declarative(&satisfies.expr),
+        Expr::TsTypeAssertion(assertion) => is_declarative(&assertion.expr),
+        _ => false,
+    }
+}
+
+// Collects the names bound by a pattern, e.g. `[a, {b, c: d}]` binds `a`, `b` and `d`.
+fn pat_idents(pat: &Pat, names: &mut Vec<JsWord>) {
+    match pat {
+        Pat::Ident(ident) => names.push(ident.id.sym.clone()),
+        Pat::Array(array) => {
+            for elem in array.elems.iter().flatten() {
+                pat_idents(elem, names);
+            }
+        }
+        Pat::Rest(rest) => pat_idents(&rest.arg, names),
+        Pat::Object(object) => {
+            for prop in &object.props {
+                match prop {
+                    ObjectPatProp::KeyValue(kv) => pat_idents(&kv.value, names),
+                    ObjectPatProp::Assign(assign) => names.push(assign.key.sym.clone()),
+                    ObjectPatProp::Rest(rest) => pat_idents(&rest.arg, names),
+                }
+            }
+        }
+        Pat::Assign(assign) => pat_idents(&assign.left, names),
+        Pat::Invalid(_) | Pat::Expr(_) => {}
+    }
+}
+
+// Collects the names bound by a declaration. `var`s are hoisted to the enclosing function,
+// so they are only included when `include_var` is set.
+fn decl_idents(decl: &Decl, include_var: bool, names: &mut Vec<JsWord>) {
+    match decl {
+        Decl::Class(class) => names.push(class.ident.sym.clone()),
+        Decl::Fn(func) => names.push(func.ident.sym.clone()),
+        Decl::Var(var) => var_decl_idents(var, include_var, names),
+        Decl::TsInterface(interface) => names.push(interface.id.sym.clone()),
+        Decl::TsTypeAlias(alias) => names.push(alias.id.sym.clone()),
+        Decl::TsEnum(ts_enum) => names.push(ts_enum.id.sym.clone()),
+        Decl::TsModule(module) => {
+            if let TsModuleName::Ident(ident) = &module.id {
+                names.push(ident.sym.clone());
+            }
+        }
+    }
+}
+
+fn var_decl_idents(var: &VarDecl, include_var: bool, names: &mut Vec<JsWord>) {
+    if include_var || var.kind != VarDeclKind::Var {
+        for decl in &var.decls {
+            pat_idents(&decl.name, names);
+        }
+    }
+}
+
+// Collects the names declared directly in a block (`let`, `const`, `class`, `function`, ...).
+fn block_idents(stmts: &[Stmt], names: &mut Vec<JsWord>) {
+    for stmt in stmts {
+        if let Stmt::Decl(decl) = stmt {
+            decl_idents(decl, false, names);
+        }
+    }
+}
+
+// Collects the names declared in a function body, including `var`s hoisted out of nested blocks.
+fn function_body_idents(stmts: &[Stmt], names: &mut Vec<JsWord>) {
+    let mut collector = VarCollector::default();
+    stmts.visit_with(&mut collector);
+    names.append(&mut collector.names);
+    block_idents(stmts, names);
+}
+
+// Finds `var` declarations, stopping at nested functions since they start a new `var` scope.
+#[derive(Default)]
+struct VarCollector {
+    names: Vec<JsWord>,
+}
+
+impl Visit for VarCollector {
+    fn visit_var_decl(&mut self, var: &VarDecl) {
+        if var.kind == VarDeclKind::Var {
+            for decl in &var.decls {
+                pat_idents(&decl.name, &mut self.names);
+            }
+        }
+    }
+
+    fn visit_function(&mut self, _function: &Function) {}
+    fn visit_arrow_expr(&mut self, _arrow: &ArrowExpr) {}
+    fn visit_class(&mut self, _class: &Class) {}
+    fn visit_getter_prop(&mut self, _getter: &GetterProp) {}
+    fn visit_setter_prop(&mut self, _setter: &SetterProp) {}
+}
+
+// What an import specifier resolves to.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+enum Resolved {
+    File(PathBuf),
+    // Not a file on disk, e.g. a builtin
+    External,
+    // Reported as an `UnresolvedImport`
+    Unresolved,
+    // A resolution we don't handle, described for the diagnostic
+    Unexpected(String),
+}
+
+// How an import specifier of a file resolved, and what it depends on.
+#[derive(Clone, Serialize, Deserialize)]
+struct SpecifierResolution {
+    specifier: JsWord,
+    resolved: Resolved,
+    dependencies: cache::Dependencies,
+}
+
+fn resolve_specifier<FS: FileSystem>(
+    resolver: &Resolver<'_, FS>,
+    specifier: &str,
+    from: &Path,
+    resolve_options: &ResolveOptions,
+) -> (Resolved, Invalidations) {
+    let resolve_result = resolver.resolve_with_options(
+        specifier,
+        from,
+        SpecifierType::Esm,
+        clone_resolve_options(resolve_options),
+    );
+    let resolved = match resolve_result.result {
+        Ok((Resolution::Path(filename), _)) => Resolved::File(filename),
+        Ok((Resolution::Builtin(_), _)) => Resolved::External,
+        Ok((Resolution::Empty, _)) => Resolved::External,
+        Err(_) => Resolved::Unresolved,
+        resolution => Resolved::Unexpected(format!("unexpected resolution {:?}", resolution)),
+    };
+    (resolved, resolve_result.invalidations)
+}
+
+fn clone_resolve_options(resolve_options: &ResolveOptions) -> ResolveOptions {
+    ResolveOptions {
+        conditions: resolve_options.conditions,
+        custom_conditions: resolve_options.custom_conditions.clone(),
+    }
+}
+
 pub struct FileAnalyzer<'a, FS: FileSystem> {
     filename: PathBuf,
     // exported_name -> original_name
     exports: HashMap<JsWord, JsWord>,
     type_exports: HashMap<JsWord, JsWord>,
-    import_usage: &'a mut ImportUsage,
-    // local name -> file
-    namespace_imports: HashMap<JsWord, JsWord>,
+    // exported_name -> where it's exported
+    export_spans: HashMap<JsWord, Span>,
+    // file -> where it's first imported
+    import_spans: HashMap<PathBuf, Span>,
+    // (file, symbol) -> where `symbol` is first imported from `file` by name, e.g. its import
+    // specifier or `utils.symbol`
+    symbol_spans: HashMap<(PathBuf, JsWord), Span>,
+    // Specifiers that failed to resolve
+    unresolved: HashSet<JsWord>,
+    // specifier -> where it's first imported, for specifiers that failed to resolve
+    unresolved_spans: HashMap<JsWord, Span>,
+    // specifier -> how it resolved. Each specifier is only resolved once.
+    resolutions: HashMap<JsWord, SpecifierResolution>,
+    // Owner -> how imports are used there
+    import_usage: HashMap<Owner, ImportUsage>,
+    // Owner -> top-level bindings referenced there
+    local_references: HashMap<Owner, HashSet<JsWord>>,
+    // top-level binding -> owner. Bindings without one are module-level, e.g. `const a = f()`.
+    owners: HashMap<JsWord, JsWord>,
+    // The owner of the code being visited
+    owner: Owner,
+    // Imports referenced anywhere in the module
+    referenced_imports: HashSet<JsWord>,
+    // Whether the module exports through `module.exports`, `exports` or `export =`, which we
+    // don't track.
+    commonjs: bool,
+    diagnostics: Vec<Diagnostic>,
+    // Lexical scopes, innermost last. Only used to tell which names refer to module objects.
+    scopes: Vec<Scope>,
 
     export_alls: Vec<PathBuf>,
+    // exported_name -> (file, original_name)
+    reexports: HashMap<JsWord, (PathBuf, JsWord)>,
+    // exported_name -> file
+    namespace_reexports: HashMap<JsWord, PathBuf>,
 
     resolver: &'a Resolver<'a, FS>,
     resolve_options: ResolveOptions,
@@ -55,24 +319,205 @@ impl<'a, FS: FileSystem> FileAnalyzer<'a, FS> {
         filename: String,
         resolver: &'a Resolver<'a, FS>,
         resolve_options: ResolveOptions,
-        import_usage: &'a mut ImportUsage,
     ) -> Self {
         Self {
             filename: PathBuf::from(&filename),
             exports: HashMap::new(),
             type_exports: HashMap::new(),
-            namespace_imports: HashMap::new(),
+            export_spans: HashMap::new(),
+            import_spans: HashMap::new(),
+            symbol_spans: HashMap::new(),
+            unresolved: HashSet::new(),
+            unresolved_spans: HashMap::new(),
+            resolutions: HashMap::new(),
+            scopes: Vec::new(),
             export_alls: Vec::new(),
-            import_usage,
+            reexports: HashMap::new(),
+            namespace_reexports: HashMap::new(),
+            import_usage: HashMap::new(),
+            local_references: HashMap::new(),
+            owners: HashMap::new(),
+            owner: None,
+            referenced_imports: HashSet::new(),
+            commonjs: false,
+            diagnostics: Vec::new(),
             resolver,
             resolve_options,
         }
     }
 
+    fn with_scope(&mut self, names: Vec<JsWord>, visit: impl FnOnce(&mut Self)) {
+        self.scopes.push(
+            names
+                .into_iter()
+                .map(|name| (name, Binding::Local))
+                .collect(),
+        );
+        visit(self);
+        self.scopes.pop();
+    }
+
+    // Returns the innermost binding of `sym`, if it is declared at all.
+    fn binding(&self, sym: &JsWord) -> Option<&Binding> {
+        self.scopes.iter().rev().find_map(|scope| scope.get(sym))
+    }
+
+    // Returns the module `sym` refers to, if the innermost binding of `sym` is a module object.
+    fn module_binding(&self, sym: &JsWord) -> Option<JsWord> {
+        match self.binding(sym) {
+            Some(Binding::Module(file)) => Some(file.clone()),
+            _ => None,
+        }
+    }
+
+    // Updates what the innermost declaration of `sym` refers to.
+    fn bind(&mut self, sym: &JsWord, binding: Binding) {
+        let scope = match self
+            .scopes
+            .iter()
+            .rposition(|scope| scope.contains_key(sym))
+        {
+            Some(index) => &mut self.scopes[index],
+            None => match self.scopes.last_mut() {
+                Some(scope) => scope,
+                None => return,
+            },
+        };
+        scope.insert(sym.clone(), binding);
+    }
+
+    fn bind_module(&mut self, sym: &JsWord, file: JsWord) {
+        self.bind(sym, Binding::Module(file));
+    }
+
+    // Whether the innermost declaration of `sym` is in the module scope.
+    fn is_top_level(&self, sym: &JsWord) -> bool {
+        self.scopes
+            .iter()
+            .rposition(|scope| scope.contains_key(sym))
+            == Some(0)
+    }
+
+    // Makes the first of `names` the owner of all of them, returning it.
+    fn record_owner(&mut self, names: &[JsWord]) -> Owner {
+        let owner = names.first()?.clone();
+        for name in names {
+            self.owners.insert(name.clone(), owner.clone());
+        }
+        Some(owner)
+    }
+
+    fn with_owner(&mut self, owner: Owner, visit: impl FnOnce(&mut Self)) {
+        let outer = std::mem::replace(&mut self.owner, owner);
+        visit(self);
+        self.owner = outer;
+    }
+
+    // The import usage of the code being visited.
+    fn usage(&mut self) -> &mut ImportUsage {
+        self.import_usage.entry(self.owner.clone()).or_default()
+    }
+
+    // Records `obj.prop` if `obj` is a module object or an import. Returns false if `obj` is
+    // not something we track.
+    fn record_member_access(&mut self, obj: &JsWord, prop: JsWord, span: Span) -> bool {
+        match self.binding(obj).cloned() {
+            Some(Binding::Module(file)) => {
+                self.record_symbol_span(&file, prop.clone(), span);
+                self.record_import(&file, prop)
+            }
+            Some(Binding::Import(file, symbol)) => {
+                self.referenced_imports.insert(obj.clone());
+                self.record_member_import(&file, symbol, prop)
+            }
+            _ => return false,
+        }
+        true
+    }
+
+    // Records a reference to `ident` that we can't follow, e.g. `console.log(utils)`. If it is a
+    // module object or an import, all of its members may be used.
+    fn record_reference(&mut self, ident: &Ident) {
+        match self.binding(&ident.sym).cloned() {
+            Some(Binding::Module(file)) => self.record_escaped_module(&file),
+            Some(Binding::Import(file, symbol)) => {
+                self.referenced_imports.insert(ident.sym.clone());
+                self.record_import(&file, symbol)
+            }
+            Some(Binding::Local) if self.is_top_level(&ident.sym) => {
+                self.local_references
+                    .entry(self.owner.clone())
+                    .or_default()
+                    .insert(ident.sym.clone());
+            }
+            _ => {}
+        }
+    }
+
+    // Handle `export { ident }`. Exported declarations are found through `owners`, but an
+    // exported import or module object is only used if the export is.
+    fn record_export_reference(&mut self, ident: &Ident) {
+        if let None | Some(Binding::Local) = self.binding(&ident.sym) {
+            return;
+        }
+        let owner = self.record_owner(std::slice::from_ref(&ident.sym));
+        self.with_owner(owner, |this| this.record_reference(ident));
+    }
+
+    fn warn(&mut self, message: String) {
+        self.report(Severity::Warning, message);
+    }
+
+    fn error(&mut self, message: String) {
+        self.report(Severity::Error, message);
+    }
+
+    fn report(&mut self, severity: Severity, message: String) {
+        let diagnostic = Diagnostic {
+            severity,
+            file: self.filename.clone(),
+            message,
+        };
+        if !self.diagnostics.contains(&diagnostic) {
+            self.diagnostics.push(diagnostic);
+        }
+    }
+
+    // Resolves `path` relative to the current file. Returns `None` for anything that isn't a
+    // file on disk, such as builtins.
+    fn resolve(&mut self, path: &JsWord) -> Option<PathBuf> {
+        let resolved = match self.resolutions.get(path) {
+            Some(resolution) => resolution.resolved.clone(),
+            None => {
+                let (resolved, invalidations) =
+                    resolve_specifier(self.resolver, path, &self.filename, &self.resolve_options);
+                let resolution = SpecifierResolution {
+                    specifier: path.clone(),
+                    dependencies: cache::Dependencies::new(&invalidations, &resolved),
+                    resolved: resolved.clone(),
+                };
+                self.resolutions.insert(path.clone(), resolution);
+                resolved
+            }
+        };
+        match resolved {
+            Resolved::File(filename) => Some(filename),
+            Resolved::External => None,
+            // Reported as an `UnresolvedImport`.
+            Resolved::Unresolved => {
+                self.unresolved.insert(path.clone());
+                None
+            }
+            Resolved::Unexpected(message) => {
+                self.error(message);
+                None
+            }
+        }
+    }
+
     fn record_import(&mut self, path: &JsWord, symbol: JsWord) {
         // Something about this module is wonky, ignore it.
         if *path == *"csstype" {
-            println!("Got csstype: {}", symbol);
             return;
         }
         /*println!(
@@ -82,101 +527,139 @@ impl<'a, FS: FileSystem> FileAnalyzer<'a, FS> {
             self.filename.display()
         );*/
 
-        match self
-            .resolver
-            .resolve_with_options(
-                path,
-                &self.filename,
-                SpecifierType::Esm,
-                ResolveOptions {
-                    conditions: self.resolve_options.conditions.clone(),
-                    custom_conditions: self.resolve_options.custom_conditions.clone(),
-                },
-            )
-            .result
-        {
-            Ok((Resolution::Path(filename), _)) => {
-                //println!("Resolved to {}", filename.display());
-                self.import_usage
-                    .imports
-                    .entry(filename)
-                    .or_default()
-                    .insert(symbol);
-            }
-            Ok((Resolution::Builtin(_), _)) => {}
-            Ok((Resolution::Empty, _)) => {}
-            Err(err) => {
-                println!("ERROR {:?} {:?}", self.filename, err);
-            }
-            resolution => {
-                panic!("Got resolution {:?}", resolution);
+        if let Some(filename) = self.resolve(path) {
+            self.usage()
+                .imports
+                .entry(filename)
+                .or_default()
+                .insert(symbol);
+        }
+    }
+
+    fn record_member_import(&mut self, path: &JsWord, symbol: JsWord, member: JsWord) {
+        if let Some(filename) = self.resolve(path) {
+            self.usage()
+                .member_imports
+                .entry(filename)
+                .or_default()
+                .entry(symbol)
+                .or_default()
+                .insert(member);
+        }
+    }
+
+    fn record_named_import(&mut self, path: &JsWord, symbol: JsWord) {
+        if let Some(filename) = self.resolve(path) {
+            self.usage()
+                .named_imports
+                .entry(filename)
+                .or_default()
+                .insert(symbol);
+        }
+    }
+
+    fn record_escaped_module(&mut self, path: &JsWord) {
+        if let Some(filename) = self.resolve(path) {
+            self.usage().escaped_modules.insert(filename);
+        }
+    }
+
+    // Records where `path` is first imported, so findings about the import can point at it.
+    fn record_import_span(&mut self, path: &JsWord, span: Span) -> Option<PathBuf> {
+        let Some(filename) = self.resolve(path) else {
+            if self.unresolved.contains(path) {
+                self.unresolved_spans.entry(path.clone()).or_insert(span);
             }
+            return None;
+        };
+        self.import_spans.entry(filename.clone()).or_insert(span);
+        Some(filename)
+    }
+
+    // Records where `symbol` is first imported from `path`, so a missing export can point at it
+    // rather than at the whole import.
+    fn record_symbol_span(&mut self, path: &JsWord, symbol: JsWord, span: Span) {
+        if let Some(filename) = self.resolve(path) {
+            self.symbol_spans.entry((filename, symbol)).or_insert(span);
+        }
+    }
+
+    fn record_dependency(&mut self, path: &JsWord, span: Span) {
+        if let Some(filename) = self.record_import_span(path, span) {
+            self.usage().dependencies.insert(filename);
         }
     }
 
-    fn record_export(&mut self, exported_name: &JsWord, original_name: &JsWord) {
+    fn record_export(&mut self, exported_name: &JsWord, original_name: &JsWord, span: Span) {
         self.exports
             .insert(exported_name.clone(), original_name.clone());
+        self.export_spans.insert(exported_name.clone(), span);
     }
 
-    fn record_type_export(&mut self, exported_name: &JsWord, original_name: &JsWord) {
+    fn record_type_export(&mut self, exported_name: &JsWord, original_name: &JsWord, span: Span) {
         self.type_exports
             .insert(exported_name.clone(), original_name.clone());
+        self.export_spans.insert(exported_name.clone(), span);
     }
 
     fn record_export_all(&mut self, path: &JsWord) {
-        match self
-            .resolver
-            .resolve_with_options(
-                path,
-                &self.filename,
-                SpecifierType::Esm,
-                ResolveOptions {
-                    conditions: self.resolve_options.conditions.clone(),
-                    custom_conditions: self.resolve_options.custom_conditions.clone(),
-                },
-            )
-            .result
-        {
-            Ok((Resolution::Path(filename), _)) => {
-                println!("Resolved to {}", filename.display());
-                self.export_alls.push(filename);
-            }
-            Ok((Resolution::Builtin(_), _)) => {}
-            Ok((Resolution::Empty, _)) => {}
-            Err(err) => {
-                println!("ERROR {:?} {:?}", self.filename, err);
-            }
-            resolution => {
-                panic!("Got resolution {:?}", resolution);
-            }
+        if let Some(filename) = self.resolve(path) {
+            self.export_alls.push(filename);
+        }
+    }
+
+    // Handle `export { original_name as exported_name } from 'path'`. The export is recorded as
+    // usual, but using it also counts as using `original_name` from `path`.
+    fn record_reexport(
+        &mut self,
+        path: &JsWord,
+        exported_name: &JsWord,
+        original_name: &JsWord,
+        span: Span,
+    ) {
+        self.record_export(exported_name, original_name, span);
+        if let Some(filename) = self.resolve(path) {
+            self.reexports
+                .insert(exported_name.clone(), (filename, original_name.clone()));
+        }
+    }
+
+    // Handle `export * as exported_name from 'path'`. Members of the namespace are traced back to
+    // the re-exported module.
+    fn record_namespace_reexport(&mut self, path: &JsWord, exported_name: &JsWord, span: Span) {
+        self.record_export(exported_name, exported_name, span);
+        if let Some(filename) = self.resolve(path) {
+            self.namespace_reexports
+                .insert(exported_name.clone(), filename);
         }
     }
 
     // When an import object is destructured, this marks all the object keys as imported.
     fn record_destructured_import(&mut self, file: JsWord, object: &ObjectPat) {
+        for (key, _) in self.destructured_keys(object) {
+            self.record_import(&file, key);
+        }
+    }
+
+    // Returns the object keys read by a destructuring pattern and where they are, e.g. `Class`
+    // and `Fn` for `const {Class, Fn: LocalFn} = obj`.
+    fn destructured_keys(&mut self, object: &ObjectPat) -> Vec<(JsWord, Span)> {
+        let mut keys = Vec::new();
         for prop in &object.props {
             match prop {
-                ObjectPatProp::Assign(assign) => self.record_import(&file, assign.key.sym.clone()),
+                ObjectPatProp::Assign(assign) => {
+                    keys.push((assign.key.sym.clone(), assign.key.span))
+                }
                 ObjectPatProp::KeyValue(kv) => match &kv.key {
                     PropName::Ident(ident) => {
-                        self.record_import(&file, ident.sym.clone());
-                    }
-                    _ => {
-                        println!(
-                            "WARNING: {}: unhandle object prop: {:?}",
-                            self.filename.display(),
-                            prop
-                        );
+                        keys.push((ident.sym.clone(), ident.span));
                     }
+                    _ => self.warn(format!("unhandled object prop: {:?}", prop)),
                 },
-                _ => println!(
-                    "WARNING: {}: unhandle object prop: {:?}",
-                    self.filename.display(),
-                    prop
-                ),
+                _ => self.warn(format!("unhandled object prop: {:?}", prop)),
             }
         }
+        keys
     }
 
     // Record usage of:
@@ -189,7 +672,7 @@ impl<'a, FS: FileSystem> FileAnalyzer<'a, FS> {
         if let Some(filename) = self.extract_require_call(call) {
             match &member_expr.prop {
                 MemberProp::Ident(ident) => self.record_import(&filename, ident.sym.clone()),
-                _ => panic!("unhandled"),
+                _ => self.warn(format!("unhandled member: {:?}", member_expr.prop)),
             }
         }
     }
@@ -204,12 +687,12 @@ impl<'a, FS: FileSystem> FileAnalyzer<'a, FS> {
         if let Some(filename) = self.extract_import_call(call) {
             match &member_expr.prop {
                 MemberProp::Ident(ident) => self.record_import(&filename, ident.sym.clone()),
-                _ => panic!("unhandled"),
+                _ => self.warn(format!("unhandled member: {:?}", member_expr.prop)),
             }
         }
     }
 
-    fn extract_require_call(&self, call: &CallExpr) -> Option<JsWord> {
+    fn extract_require_call(&mut self, call: &CallExpr) -> Option<JsWord> {
         match &call.callee {
             Callee::Super(_) => {}
             Callee::Import(_import) => {}
@@ -217,12 +700,9 @@ impl<'a, FS: FileSystem> FileAnalyzer<'a, FS> {
             Callee::Expr(expr) => {
                 if let Expr::Ident(ref ident) = **expr {
                     if ident.sym == *"require" {
-                        match *call.args[0].expr {
+                        match *call.args.first()?.expr {
                             Expr::Lit(Lit::Str(ref file)) => return Some(file.value.clone()),
-                            _ => println!(
-                                "WARNING: {}: unhandled non-literal require",
-                                self.filename.display()
-                            ),
+                            _ => self.warn("unhandled non-literal require".into()),
                         }
                     }
                 }
@@ -231,174 +711,574 @@ impl<'a, FS: FileSystem> FileAnalyzer<'a, FS> {
         None
     }
 
-    fn extract_import_call(&self, call: &CallExpr) -> Option<JsWord> {
+    fn extract_import_call(&mut self, call: &CallExpr) -> Option<JsWord> {
         match &call.callee {
             Callee::Super(_) => {}
-            Callee::Import(_import) => match *call.args[0].expr {
+            Callee::Import(_import) => match *call.args.first()?.expr {
                 Expr::Lit(Lit::Str(ref file)) => return Some(file.value.clone()),
-                _ => println!(
-                    "WARNING: {}: unhandled non-literal require",
-                    self.filename.display()
-                ),
+                _ => self.warn("unhandled non-literal import".into()),
             },
             Callee::Expr(_expr) => {}
         }
         None
     }
-}
 
-impl<'a, FS: FileSystem> Visit for FileAnalyzer<'a, FS> {
-    fn visit_module_decl(&mut self, decl: &ModuleDecl) {
-        match decl {
-            ModuleDecl::Import(import_decl) => {
-                for specifier in &import_decl.specifiers {
-                    match specifier {
-                        ImportSpecifier::Named(named_specifier) => {
-                            let atom = match &named_specifier.imported {
-                                Some(imported) => export_name_atom(imported),
-                                None => named_specifier.local.sym.clone(),
-                            };
+    // Matches `require('file')` and `import('file')` without warning about other calls.
+    fn loaded_module(call: &CallExpr) -> Option<JsWord> {
+        match &call.callee {
+            Callee::Expr(expr) if matches!(**expr, Expr::Ident(ref ident) if ident.sym == *"require") =>
+                {}
+            Callee::Import(_) => {}
+            _ => return None,
+        }
+        match *call.args.first()?.expr {
+            Expr::Lit(Lit::Str(ref file)) => Some(file.value.clone()),
+            _ => None,
+        }
+    }
 
-                            //println!("named import from {:?}: {:?}", import_decl.src.value, atom);
-                            self.record_import(&import_decl.src.value, atom);
-                            //println!("named import from {:?}: {:?}", resolved, atom);
-                            /*self.record_import(
-                                named_specifier
-                                    .imported
-                                    .unwrap_or(named_specifier.local)
-                                    .clone(),
-                            );*/
-                        }
-                        ImportSpecifier::Default(_default_specifier) => {
-                            //println!("USING {:?}", import_decl.src.value.clone());
-                            self.record_import(&import_decl.src.value, "default".into())
+    // Visits a top-level declaration as the owner of the code in it, so the usage there only
+    // counts once the declaration is used.
+    fn visit_top_level_decl(&mut self, decl: &Decl) {
+        match decl {
+            Decl::Var(var) => {
+                for declarator in &var.decls {
+                    let owner = match &declarator.init {
+                        Some(init) if !is_declarative(init) => None,
+                        _ => {
+                            let mut names = Vec::new();
+                            pat_idents(&declarator.name, &mut names);
+                            self.record_owner(&names)
                         }
-                        ImportSpecifier::Namespace(namespace_specifier) => {
-                            self.namespace_imports.insert(
-                                namespace_specifier.local.sym.clone(),
-                                import_decl.src.value.clone(),
-                            );
+                    };
+                    self.with_owner(owner, |this| declarator.visit_with(this));
+                }
+            }
+            // Decorators run when the class is defined.
+            Decl::Class(class) if !class.class.decorators.is_empty() => decl.visit_with(self),
+            _ => {
+                let mut names = Vec::new();
+                decl_idents(decl, true, &mut names);
+                let owner = self.record_owner(&names);
+                self.with_owner(owner, |this| decl.visit_with(this));
+            }
+        }
+    }
+
+    // Matches `import('file').then(mod => ...)`, returning the file and the callback.
+    fn extract_import_then_callback<'c>(
+        &mut self,
+        call_expr: &'c CallExpr,
+    ) -> Option<(JsWord, &'c ArrowExpr)> {
+        let Callee::Expr(ref callee_expr) = call_expr.callee else {
+            return None;
+        };
+        let Expr::Member(ref member_expr) = **callee_expr else {
+            return None;
+        };
+        let MemberProp::Ident(ident_expr) = &member_expr.prop else {
+            return None;
+        };
+        if ident_expr.sym != *"then" {
+            return None;
+        }
+        let Expr::Call(ref call) = *member_expr.obj else {
+            return None;
+        };
+        let Expr::Arrow(ref arrow_expr) = *call_expr.args.first()?.expr else {
+            return None;
+        };
+        Some((self.extract_import_call(call)?, arrow_expr))
+    }
+
+    // Visits an arrow function in its own scope. If `module_param` is set, the first parameter
+    // is the module object of that file, as in `import('file').then(mod => ...)`.
+    fn visit_arrow(&mut self, arrow: &ArrowExpr, module_param: Option<JsWord>) {
+        let mut names = Vec::new();
+        for param in &arrow.params {
+            pat_idents(param, &mut names);
+        }
+        if let BlockStmtOrExpr::BlockStmt(body) = &*arrow.body {
+            function_body_idents(&body.stmts, &mut names);
+        }
+
+        self.with_scope(names, |this| {
+            if let Some(file) = module_param {
+                match arrow.params.first() {
+                    Some(Pat::Ident(binding)) => this.bind_module(&binding.id.sym, file),
+                    // import('file').then(({Enum, Fn}) => ...)
+                    Some(Pat::Object(object)) => this.record_destructured_import(file, object),
+                    _ => {}
+                }
+            }
+
+            arrow.params.visit_with(this);
+            arrow.type_params.visit_with(this);
+            arrow.return_type.visit_with(this);
+            match &*arrow.body {
+                BlockStmtOrExpr::BlockStmt(body) => body.stmts.visit_with(this),
+                BlockStmtOrExpr::Expr(expr) => expr.visit_with(this),
+            }
+        });
+    }
+}
+
+impl<'a, FS: FileSystem> Visit for FileAnalyzer<'a, FS> {
+    fn visit_module(&mut self, module: &Module) {
+        let mut collector = VarCollector::default();
+        module.body.visit_with(&mut collector);
+        let mut names = collector.names;
+        for item in &module.body {
+            match item {
+                ModuleItem::ModuleDecl(ModuleDecl::Import(import_decl)) => {
+                    for specifier in &import_decl.specifiers {
+                        match specifier {
+                            ImportSpecifier::Named(named_specifier) => {
+                                names.push(named_specifier.local.sym.clone())
+                            }
+                            ImportSpecifier::Default(default_specifier) => {
+                                names.push(default_specifier.local.sym.clone())
+                            }
+                            ImportSpecifier::Namespace(namespace_specifier) => {
+                                names.push(namespace_specifier.local.sym.clone())
+                            }
                         }
                     }
                 }
+                ModuleItem::ModuleDecl(ModuleDecl::ExportDecl(export_decl)) => {
+                    decl_idents(&export_decl.decl, true, &mut names)
+                }
+                ModuleItem::ModuleDecl(ModuleDecl::ExportDefaultDecl(export_default_decl)) => {
+                    match &export_default_decl.decl {
+                        DefaultDecl::Class(ClassExpr {
+                            ident: Some(ident), ..
+                        })
+                        | DefaultDecl::Fn(FnExpr {
+                            ident: Some(ident), ..
+                        }) => names.push(ident.sym.clone()),
+                        _ => {}
+                    }
+                }
+                ModuleItem::ModuleDecl(ModuleDecl::TsImportEquals(import_equals)) => {
+                    names.push(import_equals.id.sym.clone())
+                }
+                ModuleItem::Stmt(Stmt::Decl(decl)) => decl_idents(decl, true, &mut names),
+                _ => {}
             }
-            ModuleDecl::ExportDecl(export_decl) => {
-                match &export_decl.decl {
-                    Decl::Class(class) => {
-                        let atom = &class.ident.sym;
-                        self.record_export(atom, atom);
-                        //println!("{}: class decl {:?}", self.filename, atom.clone());
+        }
+
+        self.with_scope(names, |this| {
+            for item in &module.body {
+                if let ModuleItem::ModuleDecl(ModuleDecl::Import(import_decl)) = item {
+                    let src = &import_decl.src.value;
+                    for specifier in &import_decl.specifiers {
+                        match specifier {
+                            ImportSpecifier::Named(named_specifier) => {
+                                let atom = match &named_specifier.imported {
+                                    Some(imported) => export_name_atom(imported),
+                                    None => named_specifier.local.sym.clone(),
+                                };
+                                this.bind(
+                                    &named_specifier.local.sym,
+                                    Binding::Import(src.clone(), atom),
+                                );
+                            }
+                            ImportSpecifier::Default(default_specifier) => this.bind(
+                                &default_specifier.local.sym,
+                                Binding::Import(src.clone(), "default".into()),
+                            ),
+                            ImportSpecifier::Namespace(namespace_specifier) => {
+                                this.bind_module(&namespace_specifier.local.sym, src.clone())
+                            }
+                        }
                     }
-                    Decl::Fn(func) => {
-                        let atom = &func.ident.sym;
-                        self.record_export(atom, atom);
-                        //println!("{}: func decl {:?}", self.filename, atom.clone());
+                }
+                // import utils = require('./utils');
+                if let ModuleItem::ModuleDecl(ModuleDecl::TsImportEquals(import_equals)) = item {
+                    if let TsModuleRef::TsExternalModuleRef(module_ref) = &import_equals.module_ref
+                    {
+                        this.bind_module(&import_equals.id.sym, module_ref.expr.value.clone());
                     }
-                    Decl::Var(var) => {
-                        for decl in &var.decls {
-                            match &decl.name {
-                                Pat::Ident(ident) => {
-                                    let atom = &ident.id.sym;
-                                    self.record_export(atom, atom);
-                                    //println!("{}: var decl {:?}", self.filename, atom);
-                                }
-                                /*
-                                 * Handle
-                                 * export const [Const, Var] = ["1", "2"];
-                                 */
-                                Pat::Array(array) => {
-                                    for elem in &array.elems {
-                                        match elem {
-                                            Some(Pat::Ident(ident)) => {
-                                                let atom = &ident.id.sym;
-                                                self.record_export(atom, atom);
-                                                //println!("{}: var decl {:?}", self.filename, atom);
-                                            }
-                                            _ => panic!(
-                                                "{}: unknown array export pat: {:?}",
-                                                self.filename.display(),
-                                                elem
-                                            ),
-                                        }
-                                    }
-                                }
-                                Pat::Object(object) => {
-                                    for prop in &object.props {
-                                        match prop {
-                                        ObjectPatProp::Assign(assign_prop) => {
-                                            let atom = &assign_prop.key.sym;
-                                            self.record_export(atom, atom);
+                }
+            }
+
+            for item in &module.body {
+                match item {
+                    ModuleItem::Stmt(Stmt::Decl(decl)) => this.visit_top_level_decl(decl),
+                    _ => item.visit_with(this),
+                }
+            }
+
+            // Imports that are never referenced are still used, e.g. by a re-export or a type
+            // position we don't follow. Referenced ones were recorded where they're referenced.
+            for item in &module.body {
+                if let ModuleItem::ModuleDecl(ModuleDecl::Import(import_decl)) = item {
+                    for specifier in &import_decl.specifiers {
+                        let (local, atom) = match specifier {
+                            ImportSpecifier::Named(named_specifier) => (
+                                &named_specifier.local.sym,
+                                match &named_specifier.imported {
+                                    Some(imported) => export_name_atom(imported),
+                                    None => named_specifier.local.sym.clone(),
+                                },
+                            ),
+                            ImportSpecifier::Default(default_specifier) => {
+                                (&default_specifier.local.sym, "default".into())
+                            }
+                            ImportSpecifier::Namespace(_) => continue,
+                        };
+                        if !this.referenced_imports.contains(local) {
+                            this.record_named_import(&import_decl.src.value, atom);
+                        }
+                    }
+                }
+            }
+        });
+    }
+
+    fn visit_function(&mut self, function: &Function) {
+        let mut names = Vec::new();
+        for param in &function.params {
+            pat_idents(&param.pat, &mut names);
+        }
+        if let Some(body) = &function.body {
+            function_body_idents(&body.stmts, &mut names);
+        }
+
+        self.with_scope(names, |this| {
+            function.params.visit_with(this);
+            function.decorators.visit_with(this);
+            function.type_params.visit_with(this);
+            function.return_type.visit_with(this);
+            if let Some(body) = &function.body {
+                body.stmts.visit_with(this);
+            }
+        });
+    }
+
+    fn visit_constructor(&mut self, constructor: &Constructor) {
+        let mut names = Vec::new();
+        for param in &constructor.params {
+            match param {
+                ParamOrTsParamProp::Param(param) => pat_idents(&param.pat, &mut names),
+                ParamOrTsParamProp::TsParamProp(prop) => match &prop.param {
+                    TsParamPropParam::Ident(ident) => names.push(ident.id.sym.clone()),
+                    TsParamPropParam::Assign(assign) => pat_idents(&assign.left, &mut names),
+                },
+            }
+        }
+        if let Some(body) = &constructor.body {
+            function_body_idents(&body.stmts, &mut names);
+        }
+
+        self.with_scope(names, |this| {
+            constructor.key.visit_with(this);
+            constructor.params.visit_with(this);
+            if let Some(body) = &constructor.body {
+                body.stmts.visit_with(this);
+            }
+        });
+    }
+
+    fn visit_arrow_expr(&mut self, arrow: &ArrowExpr) {
+        self.visit_arrow(arrow, None);
+    }
+
+    fn visit_fn_expr(&mut self, fn_expr: &FnExpr) {
+        // A named function expression can refer to itself by name.
+        let names = fn_expr
+            .ident
+            .iter()
+            .map(|ident| ident.sym.clone())
+            .collect();
+        self.with_scope(names, |this| fn_expr.visit_children_with(this));
+    }
+
+    fn visit_class_expr(&mut self, class_expr: &ClassExpr) {
+        let names = class_expr
+            .ident
+            .iter()
+            .map(|ident| ident.sym.clone())
+            .collect();
+        self.with_scope(names, |this| class_expr.visit_children_with(this));
+    }
+
+    fn visit_getter_prop(&mut self, getter: &GetterProp) {
+        let mut names = Vec::new();
+        if let Some(body) = &getter.body {
+            function_body_idents(&body.stmts, &mut names);
+        }
+
+        self.with_scope(names, |this| {
+            getter.key.visit_with(this);
+            getter.type_ann.visit_with(this);
+            if let Some(body) = &getter.body {
+                body.stmts.visit_with(this);
+            }
+        });
+    }
+
+    fn visit_setter_prop(&mut self, setter: &SetterProp) {
+        let mut names = Vec::new();
+        pat_idents(&setter.param, &mut names);
+        if let Some(body) = &setter.body {
+            function_body_idents(&body.stmts, &mut names);
+        }
+
+        self.with_scope(names, |this| {
+            setter.key.visit_with(this);
+            setter.param.visit_with(this);
+            if let Some(body) = &setter.body {
+                body.stmts.visit_with(this);
+            }
+        });
+    }
+
+    fn visit_block_stmt(&mut self, block: &BlockStmt) {
+        let mut names = Vec::new();
+        block_idents(&block.stmts, &mut names);
+        self.with_scope(names, |this| block.visit_children_with(this));
+    }
+
+    fn visit_catch_clause(&mut self, catch: &CatchClause) {
+        let mut names = Vec::new();
+        if let Some(param) = &catch.param {
+            pat_idents(param, &mut names);
+        }
+        self.with_scope(names, |this| catch.visit_children_with(this));
+    }
+
+    fn visit_for_stmt(&mut self, for_stmt: &ForStmt) {
+        let mut names = Vec::new();
+        if let Some(VarDeclOrExpr::VarDecl(var)) = &for_stmt.init {
+            var_decl_idents(var, false, &mut names);
+        }
+        self.with_scope(names, |this| for_stmt.visit_children_with(this));
+    }
+
+    fn visit_for_in_stmt(&mut self, for_in: &ForInStmt) {
+        let mut names = Vec::new();
+        if let VarDeclOrPat::VarDecl(var) = &for_in.left {
+            var_decl_idents(var, false, &mut names);
+        }
+        self.with_scope(names, |this| for_in.visit_children_with(this));
+    }
+
+    fn visit_for_of_stmt(&mut self, for_of: &ForOfStmt) {
+        let mut names = Vec::new();
+        if let VarDeclOrPat::VarDecl(var) = &for_of.left {
+            var_decl_idents(var, false, &mut names);
+        }
+        self.with_scope(names, |this| for_of.visit_children_with(this));
+    }
+
+    fn visit_module_decl(&mut self, decl: &ModuleDecl) {
+        match decl {
+            // Named and default imports are recorded by `visit_module`, once we know how they're
+            // used.
+            ModuleDecl::Import(import_decl) => {
+                let src = &import_decl.src.value;
+                if import_decl.type_only {
+                    self.record_import_span(src, import_decl.span);
+                } else {
+                    self.record_dependency(src, import_decl.span);
+                }
+                for specifier in &import_decl.specifiers {
+                    match specifier {
+                        ImportSpecifier::Named(named_specifier) => {
+                            let atom = match &named_specifier.imported {
+                                Some(imported) => export_name_atom(imported),
+                                None => named_specifier.local.sym.clone(),
+                            };
+                            self.record_symbol_span(src, atom, named_specifier.span);
+                        }
+                        ImportSpecifier::Default(default_specifier) => {
+                            self.record_symbol_span(src, "default".into(), default_specifier.span)
+                        }
+                        ImportSpecifier::Namespace(_) => {}
+                    }
+                }
+            }
+            ModuleDecl::ExportDecl(export_decl) => {
+                match &export_decl.decl {
+                    Decl::Class(class) => {
+                        let atom = &class.ident.sym;
+                        self.record_export(atom, atom, class.ident.span);
+                        //println!("{}: class decl {:?}", self.filename, atom.clone());
+                    }
+                    Decl::Fn(func) => {
+                        let atom = &func.ident.sym;
+                        self.record_export(atom, atom, func.ident.span);
+                        //println!("{}: func decl {:?}", self.filename, atom.clone());
+                    }
+                    Decl::Var(var) => {
+                        for decl in &var.decls {
+                            match &decl.name {
+                                Pat::Ident(ident) => {
+                                    let atom = &ident.id.sym;
+                                    self.record_export(atom, atom, ident.id.span);
+                                    //println!("{}: var decl {:?}", self.filename, atom);
+                                }
+                                /*
+                                 * Handle
+                                 * export const [Const, Var] = ["1", "2"];
+                                 */
+                                Pat::Array(array) => {
+                                    for elem in &array.elems {
+                                        match elem {
+                                            Some(Pat::Ident(ident)) => {
+                                                let atom = &ident.id.sym;
+                                                self.record_export(atom, atom, ident.id.span);
+                                                //println!("{}: var decl {:?}", self.filename, atom);
+                                            }
+                                            // export const [, Var] = ["1", "2"];
+                                            None => {}
+                                            _ => self.warn(format!(
+                                                "unhandled array export pat: {:?}",
+                                                elem
+                                            )),
                                         }
-                                        ObjectPatProp::KeyValue(kv_pat_prop) => {
-                                            match &kv_pat_prop.key {
-                                                PropName::Ident(ident) => {
-                                                    if let Pat::Ident(binding_ident) = &*kv_pat_prop.value {
-                                                        self.record_export(&ident.sym, &binding_ident.id.sym);
-                                                    } else {
-                                                        panic!("{}: unknown object export kv_pat_prop: {:?}", self.filename.display(), kv_pat_prop);
+                                    }
+                                }
+                                Pat::Object(object) => {
+                                    for prop in &object.props {
+                                        match prop {
+                                            ObjectPatProp::Assign(assign_prop) => {
+                                                let atom = &assign_prop.key.sym;
+                                                self.record_export(
+                                                    atom,
+                                                    atom,
+                                                    assign_prop.key.span,
+                                                );
+                                            }
+                                            ObjectPatProp::KeyValue(kv_pat_prop) => {
+                                                match &kv_pat_prop.key {
+                                                    PropName::Ident(ident) => {
+                                                        if let Pat::Ident(binding_ident) =
+                                                            &*kv_pat_prop.value
+                                                        {
+                                                            self.record_export(
+                                                                &ident.sym,
+                                                                &binding_ident.id.sym,
+                                                                binding_ident.id.span,
+                                                            );
+                                                        } else {
+                                                            self.warn(format!("unhandled object export kv_pat_prop: {:?}", kv_pat_prop));
+                                                        }
                                                     }
+                                                    _ => self.warn(format!(
+                                                        "unhandled object export kv_pat_prop: {:?}",
+                                                        kv_pat_prop
+                                                    )),
                                                 }
-                                                _ => panic!("{}: unknown object export kv_pat_prop: {:?}", self.filename.display(), kv_pat_prop),
                                             }
+                                            _ => self.warn(format!(
+                                                "unhandled object export pat: {:?}",
+                                                prop
+                                            )),
                                         }
-                                        _ => panic!("{}: unknown object export pat: {:?}", self.filename.display(), prop),
                                     }
-                                    }
-                                }
-                                _ => {
-                                    panic!(
-                                        "{}: unknown decl.name: {:?}",
-                                        self.filename.display(),
-                                        decl.name
-                                    )
                                 }
+                                _ => self.warn(format!("unhandled decl.name: {:?}", decl.name)),
                             }
                         }
                     }
                     Decl::TsInterface(interface) => {
                         let atom = &interface.id.sym;
-                        self.record_type_export(atom, atom);
+                        self.record_type_export(atom, atom, interface.id.span);
                         //println!("{}: interface decl {:?}", self.filename, atom);
                     }
                     Decl::TsTypeAlias(alias) => {
                         let atom = &alias.id.sym;
-                        self.record_type_export(atom, atom);
+                        self.record_type_export(atom, atom, alias.id.span);
                         //println!("{}: type decl {:?}", self.filename, atom);
                     }
                     Decl::TsEnum(ts_enum) => {
                         let atom = &ts_enum.id.sym;
-                        self.record_export(atom, atom);
+                        self.record_export(atom, atom, ts_enum.id.span);
                         //println!("{}: enum decl {:?}", self.filename, atom);
                     }
-                    _ => {
-                        println!(
-                            "WARNING: {}: unhandled export namespace",
-                            self.filename.display()
-                        );
-                    }
+                    Decl::TsModule(module) => match &module.id {
+                        // export namespace Namespace {}
+                        TsModuleName::Ident(ident) => {
+                            self.record_export(&ident.sym, &ident.sym, ident.span)
+                        }
+                        TsModuleName::Str(_) => self.warn("unhandled export namespace".into()),
+                    },
                 }
             }
             ModuleDecl::ExportNamed(named_export) => {
+                match &named_export.src {
+                    Some(src) if named_export.type_only => {
+                        self.record_import_span(&src.value, named_export.span);
+                    }
+                    Some(src) => self.record_dependency(&src.value, named_export.span),
+                    None => {}
+                }
                 for specifier in &named_export.specifiers {
                     match specifier {
                         ExportSpecifier::Named(named_specifier) => {
                             let orig_atom = export_name_atom(&named_specifier.orig);
-                            let exported_atom = match &named_specifier.exported {
-                                Some(exported) => export_name_atom(exported),
-                                None => orig_atom.clone(),
+                            let (exported_atom, span) = match &named_specifier.exported {
+                                Some(exported) => (export_name_atom(exported), exported.span()),
+                                None => (orig_atom.clone(), named_specifier.orig.span()),
                             };
-                            self.record_export(&exported_atom, &orig_atom);
+                            match &named_export.src {
+                                // export { a as b } from './x';
+                                Some(src) => {
+                                    self.record_symbol_span(
+                                        &src.value,
+                                        orig_atom.clone(),
+                                        named_specifier.span,
+                                    );
+                                    self.record_reexport(
+                                        &src.value,
+                                        &exported_atom,
+                                        &orig_atom,
+                                        span,
+                                    )
+                                }
+                                None => {
+                                    // export { utils };
+                                    if let ModuleExportName::Ident(ident) = &named_specifier.orig {
+                                        self.record_export_reference(ident);
+                                    }
+                                    self.record_export(&exported_atom, &orig_atom, span)
+                                }
+                            }
                             //println!("{}: {:?} as {:?}", self.filename, orig_atom, exported_atom);
                             //self.record_export(named_specifier.orig.sym.clone());
                         }
                         ExportSpecifier::Namespace(namespace_specifier) => {
                             let atom = export_name_atom(&namespace_specifier.name);
-                            self.record_export(&atom, &atom);
+                            let span = namespace_specifier.name.span();
+                            // export * as Namespace from './x';
+                            match &named_export.src {
+                                Some(src) => {
+                                    self.record_namespace_reexport(&src.value, &atom, span)
+                                }
+                                None => self.record_export(&atom, &atom, span),
+                            }
                             //println!("{}: namespace {:?}", self.filename, atom);
                             //self.record_export(namespace_specifier.name.sym.clone());
                         }
-                        ExportSpecifier::Default(_default_specifier) => {
-                            self.record_export(&"default".into(), &"default".into());
+                        ExportSpecifier::Default(default_specifier) => {
+                            // export v from './x';
+                            let span = default_specifier.exported.span;
+                            match &named_export.src {
+                                Some(src) => {
+                                    self.record_symbol_span(&src.value, "default".into(), span);
+                                    self.record_reexport(
+                                        &src.value,
+                                        &default_specifier.exported.sym,
+                                        &"default".into(),
+                                        span,
+                                    )
+                                }
+                                None => {
+                                    self.record_export(&"default".into(), &"default".into(), span)
+                                }
+                            }
                             //self.record_default_export();
                             //println!("{}: default {:?}", self.filename, default_specifier);
                             //self.record_export(default_specifier.exported.sym.clone());
@@ -407,54 +1287,104 @@ impl<'a, FS: FileSystem> Visit for FileAnalyzer<'a, FS> {
                 }
             }
             ModuleDecl::ExportAll(export_all) => {
+                if export_all.type_only {
+                    self.record_import_span(&export_all.src.value, export_all.span);
+                } else {
+                    self.record_dependency(&export_all.src.value, export_all.span);
+                }
                 self.record_export_all(&export_all.src.value);
             }
-            ModuleDecl::ExportDefaultDecl(_export_default_decl) => {
-                self.record_export(&"default".into(), &"default".into())
+            ModuleDecl::ExportDefaultDecl(export_default_decl) => self.record_export(
+                &"default".into(),
+                &"default".into(),
+                export_default_decl.span,
+            ),
+            ModuleDecl::ExportDefaultExpr(export_default_expr) => self.record_export(
+                &"default".into(),
+                &"default".into(),
+                export_default_expr.span,
+            ),
+            ModuleDecl::TsImportEquals(import_equals) => {
+                // export import Class = Namespace.Class;
+                if import_equals.is_export {
+                    let atom = &import_equals.id.sym;
+                    self.record_export(atom, atom, import_equals.id.span);
+                }
+                // import utils = require('./utils');
+                if let TsModuleRef::TsExternalModuleRef(module_ref) = &import_equals.module_ref {
+                    let src = &module_ref.expr.value;
+                    if import_equals.is_type_only {
+                        self.record_import_span(src, import_equals.span);
+                    } else {
+                        self.record_dependency(src, import_equals.span);
+                    }
+                }
             }
-            ModuleDecl::ExportDefaultExpr(_export_default_decl) => {
-                self.record_export(&"default".into(), &"default".into())
+            // export = utils;
+            ModuleDecl::TsExportAssignment(_export_assignment) => self.commonjs = true,
+            // export as namespace utils;
+            ModuleDecl::TsNamespaceExport(_namespace_export) => {}
+        }
+
+        match decl {
+            ModuleDecl::ExportDecl(export_decl) => self.visit_top_level_decl(&export_decl.decl),
+            ModuleDecl::ExportDefaultDecl(export_default_decl) => {
+                let mut names = vec!["default".into()];
+                match &export_default_decl.decl {
+                    DefaultDecl::Class(class) if !class.class.decorators.is_empty() => {
+                        names.clear()
+                    }
+                    DefaultDecl::Class(ClassExpr {
+                        ident: Some(ident), ..
+                    })
+                    | DefaultDecl::Fn(FnExpr {
+                        ident: Some(ident), ..
+                    }) => names.push(ident.sym.clone()),
+                    DefaultDecl::TsInterfaceDecl(interface) => names.push(interface.id.sym.clone()),
+                    _ => {}
+                }
+                let owner = self.record_owner(&names);
+                self.with_owner(owner, |this| decl.visit_children_with(this));
             }
-            _ => {
-                println!(
-                    "WARNING: {}: unhandled ModuleDecl {:?}",
-                    self.filename.display(),
-                    decl
-                );
+            // export default () => {};
+            ModuleDecl::ExportDefaultExpr(export_default_expr)
+                if is_declarative(&export_default_expr.expr) =>
+            {
+                let owner = self.record_owner(&["default".into()]);
+                self.with_owner(owner, |this| decl.visit_children_with(this));
             }
+            _ => decl.visit_children_with(self),
         }
-        decl.visit_children_with(self);
     }
 
     fn visit_var_declarator(&mut self, var: &VarDeclarator) {
         if let Some(ref init) = var.init {
-            let filename = match **init {
-                Expr::Ident(ref ident) => {
-                    /*
-                    Handle the following:
-
-                    import * as utils from 'testdata/export_named.ts';
-                    const {Class, Fn} = utils;
-                    */
-
-                    match self.namespace_imports.get(&ident.sym) {
-                        Some(file) => match &var.name {
-                            Pat::Object(object) => {
-                                self.record_destructured_import(file.clone(), object);
-                                None
-                            }
-                            _ => {
-                                println!(
-                                    "WARNING: {}: unhandled var name: {:?}",
-                                    self.filename.display(),
-                                    var.name
-                                );
-                                return;
+            if let Expr::Ident(ref ident) = **init {
+                if let Some(binding @ (Binding::Module(_) | Binding::Import(..))) =
+                    self.binding(&ident.sym).cloned()
+                {
+                    match &var.name {
+                        // const alias = utils;
+                        Pat::Ident(alias) => self.bind(&alias.id.sym, binding),
+                        /*
+                        Handle the following:
+
+                        import * as utils from 'testdata/export_named.ts';
+                        const {Class, Fn} = utils;
+                        */
+                        Pat::Object(object) => {
+                            for (key, span) in self.destructured_keys(object) {
+                                self.record_member_access(&ident.sym, key, span);
                             }
-                        },
-                        None => None,
+                        }
+                        _ => self.record_reference(ident),
                     }
+                    var.name.visit_with(self);
+                    return;
                 }
+            }
+
+            let filename = match **init {
                 // const named = require('testdata/export_named.ts');
                 Expr::Call(ref call) => self.extract_require_call(call),
                 // const named = require('testdata/export_named.ts') as typeof import('testdata/export_named.ts');
@@ -473,47 +1403,58 @@ impl<'a, FS: FileSystem> Visit for FileAnalyzer<'a, FS> {
             if let Some(filename) = filename {
                 match &var.name {
                     // const named = require('testdata/export_named.ts');
-                    Pat::Ident(binding) => {
-                        self.namespace_imports
-                            .insert(binding.id.sym.clone(), filename);
-                    }
+                    Pat::Ident(binding) => self.bind_module(&binding.id.sym, filename),
                     // const {Enum, Fn} = require('testdata/export_named.ts');
                     Pat::Object(object) => self.record_destructured_import(filename, object),
-                    _ => todo!("fuck"),
+                    _ => self.warn(format!("unhandled var name: {:?}", var.name)),
                 }
             }
-
-            var.visit_children_with(self);
-
-            // TODO(zbarsky): pop bindings we added!
-        } else {
-            var.visit_children_with(self);
         }
+
+        var.visit_children_with(self);
     }
 
     fn visit_member_expr(&mut self, member_expr: &MemberExpr) {
-        if !self.namespace_imports.is_empty() {
-            if let Expr::Ident(Ident { ref sym, .. }) = *member_expr.obj {
-                /*
-                Handle the following:
-
-                import * as utils from 'testdata/export_named.ts';
-                utils.Const;
-                */
-                if let Some(file) = self.namespace_imports.get(sym) {
-                    match &member_expr.prop {
-                        MemberProp::Ident(ident) => {
-                            let file = file.clone();
-                            self.record_import(&file, ident.sym.clone());
-                        }
-                        _ => println!(
-                            "WARNING: {}: unhandled MemberExpr: {:?}",
-                            self.filename.display(),
-                            member_expr
-                        ),
+        match *member_expr.obj {
+            /*
+            Handle the following:
+
+            import * as utils from 'testdata/export_named.ts';
+            utils.Const;
+
+            import { Namespace } from 'testdata/export_namespace.ts';
+            Namespace.Class;
+            */
+            Expr::Ident(ref obj) => {
+                if let Some(prop) = member_prop_atom(&member_expr.prop) {
+                    if self.record_member_access(&obj.sym, prop, member_expr.prop.span()) {
+                        member_expr.prop.visit_with(self);
+                        return;
+                    }
+                }
+            }
+            /*
+            Handle the following:
+
+            import * as utils from 'testdata/export_namespace.ts';
+            utils.Namespace.Class;
+            */
+            Expr::Member(ref inner) => {
+                if let (Expr::Ident(ref obj), Some(symbol), Some(member)) = (
+                    &*inner.obj,
+                    member_prop_atom(&inner.prop),
+                    member_prop_atom(&member_expr.prop),
+                ) {
+                    if let Some(file) = self.module_binding(&obj.sym) {
+                        self.record_named_import(&file, symbol.clone());
+                        self.record_member_import(&file, symbol, member);
+                        inner.prop.visit_with(self);
+                        member_expr.prop.visit_with(self);
+                        return;
                     }
                 }
             }
+            _ => {}
         }
 
         match *member_expr.obj {
@@ -527,19 +1468,17 @@ impl<'a, FS: FileSystem> Visit for FileAnalyzer<'a, FS> {
             Expr::Paren(ref paren_expr) => {
                 match *paren_expr.expr {
                     // (require('testdata/export_named.ts') as import('testdata/export_named.ts')).Interface
-                    Expr::TsAs(ref as_expr) => match *as_expr.expr {
-                        Expr::Call(ref call) => {
+                    Expr::TsAs(ref as_expr) => {
+                        if let Expr::Call(ref call) = *as_expr.expr {
                             self.handle_potential_require_call_member_expr(call, member_expr)
                         }
-                        _ => {}
-                    },
+                    }
                     // (await import('testdata/export_named.ts')).Interface;
-                    Expr::Await(ref await_expr) => match *await_expr.arg {
-                        Expr::Call(ref call) => {
+                    Expr::Await(ref await_expr) => {
+                        if let Expr::Call(ref call) = *await_expr.arg {
                             self.handle_potential_import_call_member_expr(call, member_expr)
                         }
-                        _ => {}
-                    },
+                    }
                     _ => {}
                 }
             }
@@ -550,39 +1489,94 @@ impl<'a, FS: FileSystem> Visit for FileAnalyzer<'a, FS> {
     }
 
     fn visit_call_expr(&mut self, call_expr: &CallExpr) {
-        let mut sym = None;
-        let mut filename = None;
+        // import('./module'); require('./module');
+        if let Some(path) = Self::loaded_module(call_expr) {
+            self.record_dependency(&path, call_expr.span);
+        }
+
         // import('testdata/export_named.ts').then(mod => mod.Enum);
-        if let Callee::Expr(ref callee_expr) = call_expr.callee {
-            if let Expr::Member(ref member_expr) = **callee_expr {
-                if let MemberProp::Ident(ident_expr) = &member_expr.prop {
-                    if ident_expr.sym == *"then" {
-                        if let Expr::Call(ref call) = *member_expr.obj {
-                            //println!("call: {:?}", call_expr);
-                            if let Some(arg) = call_expr.args.get(0) {
-                                if let Expr::Arrow(ref arrow_expr) = *arg.expr {
-                                    if let Some(Pat::Ident(ref ident)) = arrow_expr.params.get(0) {
-                                        sym = Some(ident.id.sym.clone());
-                                        filename = self.extract_import_call(call);
-                                    }
-                                }
-                            }
-                        }
-                    }
+        match self.extract_import_then_callback(call_expr) {
+            Some((filename, arrow_expr)) => {
+                call_expr.callee.visit_with(self);
+                self.visit_arrow(arrow_expr, Some(filename));
+                for arg in &call_expr.args[1..] {
+                    arg.visit_with(self);
                 }
+                call_expr.type_args.visit_with(self);
             }
+            None => call_expr.visit_children_with(self),
         }
+    }
 
-        match (sym, filename) {
-            (Some(sym), Some(filename)) => {
-                let prev_binding = self.namespace_imports.insert(sym.clone(), filename);
-                call_expr.visit_children_with(self);
-                if let Some(prev_binding) = prev_binding {
-                    self.namespace_imports.insert(sym, prev_binding);
+    fn visit_expr(&mut self, expr: &Expr) {
+        match expr {
+            Expr::Ident(ident) => {
+                // exports.Class = Class;
+                if ident.sym == *"exports" && self.binding(&ident.sym).is_none() {
+                    self.commonjs = true;
                 }
+                self.record_reference(ident);
+            }
+            // module.exports = { Class };
+            Expr::Member(MemberExpr {
+                obj,
+                prop: MemberProp::Ident(prop),
+                ..
+            }) if prop.sym == *"exports" => {
+                if let Expr::Ident(ref obj) = **obj {
+                    if obj.sym == *"module" && self.binding(&obj.sym).is_none() {
+                        self.commonjs = true;
+                    }
+                }
+            }
+            _ => {}
+        }
+        expr.visit_children_with(self);
+    }
+
+    fn visit_prop(&mut self, prop: &Prop) {
+        // const obj = { utils };
+        if let Prop::Shorthand(ident) = prop {
+            self.record_reference(ident);
+        }
+        prop.visit_children_with(self);
+    }
+
+    fn visit_ts_qualified_name(&mut self, name: &TsQualifiedName) {
+        // let value: utils.Interface;
+        if let TsEntityName::Ident(ref obj) = name.left {
+            if self.record_member_access(&obj.sym, name.right.sym.clone(), name.right.span) {
+                return;
+            }
+        }
+        name.visit_children_with(self);
+    }
+
+    fn visit_ts_entity_name(&mut self, name: &TsEntityName) {
+        // let value: Interface;
+        if let TsEntityName::Ident(ident) = name {
+            self.record_reference(ident);
+        }
+        name.visit_children_with(self);
+    }
+
+    fn visit_jsx_element_name(&mut self, name: &JSXElementName) {
+        // <Component />
+        if let JSXElementName::Ident(ident) = name {
+            self.record_reference(ident);
+        }
+        name.visit_children_with(self);
+    }
+
+    fn visit_jsx_member_expr(&mut self, expr: &JSXMemberExpr) {
+        // <utils.Component />
+        if let JSXObject::Ident(ref obj) = expr.obj {
+            if self.record_member_access(&obj.sym, expr.prop.sym.clone(), expr.prop.span) {
+                return;
             }
-            _ => call_expr.visit_children_with(self),
+            self.record_reference(obj);
         }
+        expr.visit_children_with(self);
     }
 
     /*fn visit_module_item(&mut self, n: &ModuleItem) {
@@ -590,175 +1584,1054 @@ impl<'a, FS: FileSystem> Visit for FileAnalyzer<'a, FS> {
     }*/
 }
 
+#[derive(Clone, Serialize, Deserialize)]
 pub struct ModuleExports {
     // exported_name -> original_name
     exports: HashMap<JsWord, JsWord>,
     type_exports: HashMap<JsWord, JsWord>,
     export_alls: Vec<PathBuf>,
+    // exported_name -> (file, original_name)
+    reexports: HashMap<JsWord, (PathBuf, JsWord)>,
+    // exported_name -> file
+    namespace_reexports: HashMap<JsWord, PathBuf>,
+    // Exports through `module.exports` and the like, so there may be exports we don't know of
+    commonjs: bool,
+    // exported_name -> where it's exported, from start to end
+    locations: HashMap<JsWord, (Location, Location)>,
 }
 
-#[derive(Default, Debug, PartialEq)]
-pub struct ModuleResults {
-    pub unused_exports: HashSet<JsWord>,
-    pub unused_type_exports: HashSet<JsWord>,
+#[derive(Clone, Serialize, Deserialize)]
+pub struct ModuleImports {
+    // Owner -> how imports are used there
+    #[serde(with = "cache::pairs")]
+    usage: HashMap<Owner, ImportUsage>,
+    // Owner -> top-level bindings referenced there
+    #[serde(with = "cache::pairs")]
+    local_references: HashMap<Owner, HashSet<JsWord>>,
+    // top-level binding -> owner
+    owners: HashMap<JsWord, JsWord>,
+    // file -> where it's first imported
+    import_locations: HashMap<PathBuf, Location>,
+    // (file, symbol) -> where `symbol` is first imported from `file` by name
+    #[serde(with = "cache::pairs")]
+    symbol_locations: HashMap<(PathBuf, JsWord), Location>,
 }
-pub type AnalysisResults = HashMap<PathBuf, ModuleResults>;
-
-pub struct Analyzer {
-    import_usage: ImportUsage,
 
-    cm: Lrc<SourceMap>,
-    handler: Handler,
+// A position in a source file. Lines and columns start at 1, and columns count characters.
+#[derive(
+    Clone, Copy, Debug, Default, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize,
+)]
+pub struct Location {
+    pub line: usize,
+    pub column: usize,
+}
 
-    exports: HashMap<PathBuf, ModuleExports>,
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize)]
+pub struct UnusedExport {
+    pub exported_name: JsWord,
+    // The name of the exported binding, e.g. `a` for `export { a as b }`
+    pub original_name: JsWord,
+    pub location: Location,
+    // Where the exported name ends, e.g. for editors to highlight it
+    pub end_location: Location,
+    // Whether live code in the module uses the export, so only the `export` keyword is
+    // unnecessary. Otherwise the declaration is dead.
+    pub used_in_module: bool,
+}
 
-    resolve_options: ResolveOptions,
+// Unused exports of a module, in source order.
+#[derive(Default, Debug, PartialEq, Serialize)]
+pub struct ModuleResults {
+    pub unused_exports: Vec<UnusedExport>,
+    pub unused_type_exports: Vec<UnusedExport>,
+    // Exports, values or types, that only test files use. They're dead in production, but
+    // can't be removed without changing the tests.
+    pub test_only_exports: Vec<UnusedExport>,
 }
 
-impl Analyzer {
-    pub fn new(resolve_options: ResolveOptions) -> Self {
-        let cm: Lrc<SourceMap> = Default::default();
-        Self {
-            import_usage: ImportUsage::new(),
-            handler: Handler::with_tty_emitter(ColorConfig::Auto, true, false, Some(cm.clone())),
-            exports: HashMap::new(),
-            resolve_options,
-            cm,
-        }
+impl ModuleResults {
+    pub fn is_empty(&self) -> bool {
+        self.unused_exports.is_empty()
+            && self.unused_type_exports.is_empty()
+            && self.test_only_exports.is_empty()
     }
+}
 
-    pub fn add_file<'a, FS: FileSystem>(&mut self, resolver: &Resolver<'a, FS>, file_path: &Path) {
-        // Parse the file into an AST
-        //println!("loading file {:?}", file_path);
-        let fm = self.cm.load_file(file_path).expect("failed to load file");
-
-        // Create a visitor to traverse the ASTs and record imported and exported symbols
-        let mut visitor = FileAnalyzer::new(
-            file_path.to_str().unwrap().to_owned(),
-            resolver,
-            ResolveOptions {
-                conditions: self.resolve_options.conditions.clone(),
-                custom_conditions: self.resolve_options.custom_conditions.clone(),
-            },
-            &mut self.import_usage,
-        );
+// An import of a name the imported module doesn't export.
+#[derive(Debug, PartialEq, Eq, Hash, Serialize)]
+pub struct MissingExport {
+    // The module importing or re-exporting `symbol`
+    pub importer: PathBuf,
+    // The module `symbol` is imported from
+    pub source: PathBuf,
+    pub symbol: JsWord,
+    // Where `importer` imports `symbol`, or `source` if we don't know where it names `symbol`
+    pub location: Location,
+}
 
-        let lexer = Lexer::new(
-            // We want to parse ecmascript
-            Syntax::Typescript(TsConfig {
-                tsx: true,
-                decorators: true,
-                ..Default::default()
-            }),
-            // EsVersion defaults to es5
-            Default::default(),
-            StringInput::from(&*fm),
-            None,
-        );
+// An import whose specifier doesn't resolve to a file, e.g. a typo or a missing package.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct UnresolvedImport {
+    pub importer: PathBuf,
+    pub specifier: JsWord,
+    pub location: Location,
+}
 
-        let mut parser = Parser::new_from(lexer);
+// A suppression comment that no longer suppresses any finding.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize)]
+pub struct StaleSuppression {
+    pub file: PathBuf,
+    // `IGNORE_NEXT_LINE` or `DISABLE`
+    pub directive: String,
+    pub location: Location,
+}
 
-        for e in parser.take_errors() {
-            e.into_diagnostic(&self.handler).emit();
-        }
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    // Part of a file uses code we can't follow, so its results may be off.
+    Warning,
+    // A file couldn't be analyzed at all.
+    Error,
+}
 
-        let module = parser
-            .parse_module()
-            .map_err(|e| {
-                // Unrecoverable fatal error occurred
-                e.into_diagnostic(&self.handler).emit()
-            })
-            .expect("failed to parser module");
+// Something that kept us from analyzing part of a file, e.g. a pattern we don't handle.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub file: PathBuf,
+    pub message: String,
+}
 
-        // Traverse the AST and record imported and exported symbols
-        module.visit_with(&mut visitor);
+#[derive(Default, Debug, PartialEq, Serialize)]
+pub struct AnalysisResults {
+    // Modules with unused exports
+    pub modules: HashMap<PathBuf, ModuleResults>,
+    // Modules that are neither entry points nor loaded by any other module
+    pub unused_files: HashSet<PathBuf>,
+    pub missing_exports: HashSet<MissingExport>,
+    pub unresolved_imports: HashSet<UnresolvedImport>,
+    pub stale_suppressions: HashSet<StaleSuppression>,
+    // By file, and in the order they were found in it
+    pub diagnostics: Vec<Diagnostic>,
+}
 
-        //println!("done with {:?}", visitor.filename);
-        self.exports.insert(
-            visitor.filename.into(),
-            ModuleExports {
-                exports: visitor.exports,
-                type_exports: visitor.type_exports,
-                export_alls: visitor.export_alls,
-            },
-        );
+impl AnalysisResults {
+    // Keeps the findings for which `keep(file, symbol, kind)` is true, with the file and symbol
+    // of their `BaselineEntry`. Diagnostics are kept.
+    pub fn retain(&mut self, mut keep: impl FnMut(&Path, &str, FindingKind) -> bool) {
+        for (file, module_results) in self.modules.iter_mut() {
+            module_results
+                .unused_exports
+                .retain(|export| keep(file, &export.exported_name, FindingKind::UnusedExport));
+            module_results
+                .unused_type_exports
+                .retain(|export| keep(file, &export.exported_name, FindingKind::UnusedTypeExport));
+            module_results
+                .test_only_exports
+                .retain(|export| keep(file, &export.exported_name, FindingKind::TestOnlyExport));
+        }
+        self.modules
+            .retain(|_, module_results| !module_results.is_empty());
+        self.unused_files
+            .retain(|file| keep(file, "", FindingKind::UnusedFile));
+        self.missing_exports.retain(|missing| {
+            keep(
+                &missing.importer,
+                &missing.symbol,
+                FindingKind::MissingExport,
+            )
+        });
+        self.unresolved_imports.retain(|unresolved| {
+            keep(
+                &unresolved.importer,
+                &unresolved.specifier,
+                FindingKind::UnresolvedImport,
+            )
+        });
+        self.stale_suppressions
+            .retain(|stale| keep(&stale.file, &stale.directive, FindingKind::StaleSuppression));
     }
+}
 
-    pub fn finalize(self) -> AnalysisResults {
-        let mut results = AnalysisResults::new();
-        for (file, exports) in &self.exports {
-            let mut module_results = ModuleResults {
-                unused_exports: HashSet::new(),
-                unused_type_exports: HashSet::new(),
-            };
-            let imports = self.import_usage.imports.get(file);
+// How an imported symbol is used.
+#[derive(Clone, PartialEq, Eq, Hash)]
+enum Access {
+    // Bound by `import { symbol }`, which on its own doesn't use any of its members.
+    Named,
+    // Through a member, e.g. `Namespace.Class`.
+    Member(JsWord),
+    // As a whole, so every member may be used.
+    Whole,
+}
 
-            for (exported_name, original_name) in &exports.exports {
-                //if !imports.is_some_and(|v| v.contains(&exported_name)) {
-                let used = match imports {
-                    Some(v) => v.contains(&exported_name),
-                    None => false,
-                };
-                if !used {
-                    module_results
-                        .unused_exports
-                        .insert(original_name.to_owned());
-                }
+// (importer, file, symbol, access): `importer` uses `symbol` as exported by `file`.
+type PendingImport = (PathBuf, PathBuf, JsWord, Access);
+
+// What `trace_imports` found.
+struct Trace {
+    // file -> exported names that are used
+    used: HashMap<PathBuf, HashSet<JsWord>>,
+    // Code whose usage counts
+    live: HashSet<(PathBuf, Owner)>,
+    // Modules loaded by live code in another module
+    loaded: HashSet<PathBuf>,
+    missing_exports: HashSet<MissingExport>,
+}
+
+// A comment that suppresses the findings on the line after it.
+pub const IGNORE_NEXT_LINE: &str = "ts-deadcode-ignore-next-line";
+// A comment that suppresses every finding in its file.
+pub const DISABLE: &str = "ts-deadcode-disable";
+// A JSDoc tag for exports that are used from outside the repo, e.g. by other repos or through
+// reflection.
+pub const PUBLIC_TAG: &str = "@public";
+
+// What the comments of a file say about its findings.
+#[derive(Clone, Default, Serialize, Deserialize)]
+struct Suppressions {
+    // Where the `DISABLE` comment is
+    disable: Option<Location>,
+    // Where each `IGNORE_NEXT_LINE` comment is, and the line it suppresses findings on
+    ignore_next_line: Vec<(Location, usize)>,
+    // Exports documented with `PUBLIC_TAG`, which count as used
+    public_exports: Vec<JsWord>,
+}
+
+impl Suppressions {
+    // Directives have to be the first word of a comment, but may be followed by an explanation,
+    // e.g. `// ts-deadcode-ignore-next-line: loaded by the plugin system`.
+    fn collect(cm: &SourceMap, module: &Module, comments: &SingleThreadedComments) -> Self {
+        let mut suppressions = Suppressions::default();
+        let (leading, trailing) = comments.borrow_all();
+        let mut all: Vec<&Comment> = leading
+            .values()
+            .chain(trailing.values())
+            .flatten()
+            .collect();
+        all.sort_by_key(|comment| comment.span.lo);
+        for comment in all {
+            let directive = comment.text.split_whitespace().next().unwrap_or_default();
+            let directive = directive.trim_end_matches(':');
+            if directive == IGNORE_NEXT_LINE {
+                let next_line = location(cm, comment.span.hi).line + 1;
+                suppressions
+                    .ignore_next_line
+                    .push((location(cm, comment.span.lo), next_line));
+            } else if directive == DISABLE && suppressions.disable.is_none() {
+                suppressions.disable = Some(location(cm, comment.span.lo));
             }
+        }
 
-            for (exported_name, original_name) in &exports.type_exports {
-                //if !imports.is_some_and(|v| v.contains(&exported_name)) {
-                let used = match imports {
-                    Some(v) => v.contains(&exported_name),
-                    None => false,
-                };
-                if !used {
-                    module_results
-                        .unused_type_exports
-                        .insert(original_name.to_owned());
+        for item in &module.body {
+            let ModuleItem::ModuleDecl(decl) = item else {
+                continue;
+            };
+            let is_public = comments.with_leading(decl.span().lo, |comments| {
+                comments.iter().any(|comment| {
+                    // Only JSDoc comments, i.e. `/** ... */`
+                    comment.kind == CommentKind::Block
+                        && comment.text.starts_with('*')
+                        && comment
+                            .text
+                            .split_whitespace()
+                            .any(|word| word == PUBLIC_TAG)
+                })
+            });
+            if is_public {
+                exported_names(decl, &mut suppressions.public_exports);
+            }
+        }
+        suppressions
+    }
+}
+
+// The names a declaration exports, e.g. `a` and `c` for `export { a, b as c }`.
+fn exported_names(decl: &ModuleDecl, names: &mut Vec<JsWord>) {
+    match decl {
+        ModuleDecl::ExportDecl(export_decl) => decl_idents(&export_decl.decl, true, names),
+        ModuleDecl::ExportDefaultDecl(_) | ModuleDecl::ExportDefaultExpr(_) => {
+            names.push("default".into())
+        }
+        ModuleDecl::ExportNamed(named_export) => {
+            for specifier in &named_export.specifiers {
+                names.push(match specifier {
+                    ExportSpecifier::Named(named) => {
+                        export_name_atom(named.exported.as_ref().unwrap_or(&named.orig))
+                    }
+                    ExportSpecifier::Namespace(namespace) => export_name_atom(&namespace.name),
+                    ExportSpecifier::Default(default) => default.exported.sym.clone(),
+                });
+            }
+        }
+        _ => {}
+    }
+}
+
+// What a single file contributes to the analysis. Files are analyzed independently of each
+// other, so their facts can be collected in parallel and merged into the `Analyzer` afterwards.
+#[derive(Clone, Serialize, Deserialize)]
+struct FileFacts {
+    filename: PathBuf,
+    // `None` if the file couldn't be loaded or parsed
+    modules: Option<(ModuleImports, ModuleExports)>,
+    unresolved_imports: Vec<UnresolvedImport>,
+    diagnostics: Vec<Diagnostic>,
+    // How the file's imports resolved, sorted by specifier
+    resolutions: Vec<SpecifierResolution>,
+    suppressions: Suppressions,
+}
+
+impl FileFacts {
+    // Parses and visits `source`, the contents of `file_path`. Each file gets its own
+    // `SourceMap`, since they can't be shared between threads.
+    fn collect<FS: FileSystem>(
+        resolver: &Resolver<'_, FS>,
+        file_path: &Path,
+        source: io::Result<String>,
+        resolve_options: &ResolveOptions,
+    ) -> Self {
+        let mut facts = FileFacts {
+            filename: file_path.into(),
+            modules: None,
+            unresolved_imports: Vec::new(),
+            diagnostics: Vec::new(),
+            resolutions: Vec::new(),
+            suppressions: Suppressions::default(),
+        };
+        let cm: Lrc<SourceMap> = Default::default();
+        let handler = Handler::with_tty_emitter(ColorConfig::Auto, true, false, Some(cm.clone()));
+
+        // Parse the file into an AST
+        let fm = match source {
+            Ok(source) => cm.new_source_file(FileName::Real(file_path.into()), source),
+            Err(err) => {
+                facts.fail(format!("failed to load file: {}", err));
+                return facts;
+            }
+        };
+
+        // Create a visitor to traverse the ASTs and record imported and exported symbols
+        let mut visitor = FileAnalyzer::new(
+            file_path.to_str().unwrap().to_owned(),
+            resolver,
+            clone_resolve_options(resolve_options),
+        );
+
+        let comments = SingleThreadedComments::default();
+        let lexer = Lexer::new(
+            syntax(),
+            // EsVersion defaults to es5
+            Default::default(),
+            StringInput::from(&*fm),
+            Some(&comments),
+        );
+
+        let mut parser = Parser::new_from(lexer);
+
+        for e in parser.take_errors() {
+            e.into_diagnostic(&handler).emit();
+        }
+
+        let module = match parser.parse_module() {
+            Ok(module) => module,
+            // Unrecoverable fatal error occurred
+            Err(e) => {
+                let message = format!("failed to parse: {}", e.kind().msg());
+                e.into_diagnostic(&handler).emit();
+                facts.fail(message);
+                return facts;
+            }
+        };
+
+        // Traverse the AST and record imported and exported symbols
+        module.visit_with(&mut visitor);
+        facts.suppressions = Suppressions::collect(&cm, &module, &comments);
+
+        facts.diagnostics = visitor.diagnostics;
+        facts.resolutions = visitor.resolutions.into_values().collect();
+        facts
+            .resolutions
+            .sort_by(|a, b| a.specifier.cmp(&b.specifier));
+        facts.unresolved_imports = locations(&cm, visitor.unresolved_spans)
+            .into_iter()
+            .map(|(specifier, location)| UnresolvedImport {
+                importer: visitor.filename.clone(),
+                specifier,
+                location,
+            })
+            .collect();
+        let imports = ModuleImports {
+            usage: visitor.import_usage,
+            local_references: visitor.local_references,
+            owners: visitor.owners,
+            import_locations: locations(&cm, visitor.import_spans),
+            symbol_locations: locations(&cm, visitor.symbol_spans),
+        };
+        let exports = ModuleExports {
+            exports: visitor.exports,
+            type_exports: visitor.type_exports,
+            export_alls: visitor.export_alls,
+            reexports: visitor.reexports,
+            namespace_reexports: visitor.namespace_reexports,
+            commonjs: visitor.commonjs,
+            locations: ranges(&cm, visitor.export_spans),
+        };
+        facts.modules = Some((imports, exports));
+        facts
+    }
+
+    // Records that the file couldn't be analyzed, so the results are incomplete.
+    fn fail(&mut self, message: String) {
+        self.diagnostics.push(Diagnostic {
+            severity: Severity::Error,
+            file: self.filename.clone(),
+            message,
+        });
+    }
+}
+
+fn locations<K: Eq + Hash>(cm: &SourceMap, spans: HashMap<K, Span>) -> HashMap<K, Location> {
+    spans
+        .into_iter()
+        .map(|(key, span)| (key, location(cm, span.lo)))
+        .collect()
+}
+
+fn ranges<K: Eq + Hash>(
+    cm: &SourceMap,
+    spans: HashMap<K, Span>,
+) -> HashMap<K, (Location, Location)> {
+    spans
+        .into_iter()
+        .map(|(key, span)| (key, (location(cm, span.lo), location(cm, span.hi))))
+        .collect()
+}
+
+fn location(cm: &SourceMap, pos: BytePos) -> Location {
+    let loc = cm.lookup_char_pos(pos);
+    Location {
+        line: loc.line,
+        column: loc.col.0 + 1,
+    }
+}
+
+pub struct Analyzer {
+    exports: HashMap<PathBuf, ModuleExports>,
+    imports: HashMap<PathBuf, ModuleImports>,
+    // Files that are used from outside the analyzed code. If empty, every file is.
+    entry_points: HashSet<PathBuf>,
+    test_files: HashSet<PathBuf>,
+    suppressions: HashMap<PathBuf, Suppressions>,
+    // importer -> its imports that don't resolve
+    unresolved_imports: HashMap<PathBuf, Vec<UnresolvedImport>>,
+    // file -> the problems found in it, or reported for it with `add_diagnostic`
+    diagnostics: HashMap<PathBuf, Vec<Diagnostic>>,
+    // file -> what the resolution of each of its imports depends on
+    dependencies: HashMap<PathBuf, Vec<cache::Dependencies>>,
+    // file -> contents to analyze instead of the file on disk, e.g. unsaved changes in an editor
+    overlays: HashMap<PathBuf, String>,
+
+    resolve_options: ResolveOptions,
+}
+
+impl Analyzer {
+    pub fn new(resolve_options: ResolveOptions) -> Self {
+        Self {
+            exports: HashMap::new(),
+            imports: HashMap::new(),
+            entry_points: HashSet::new(),
+            test_files: HashSet::new(),
+            suppressions: HashMap::new(),
+            unresolved_imports: HashMap::new(),
+            diagnostics: HashMap::new(),
+            dependencies: HashMap::new(),
+            overlays: HashMap::new(),
+            resolve_options,
+        }
+    }
+
+    // Analyzes `source` instead of the contents of `file_path` on disk. Has to come before the
+    // file is added, or added again.
+    pub fn add_overlay(&mut self, file_path: &Path, source: String) {
+        self.overlays.insert(file_path.into(), source);
+    }
+
+    pub fn remove_overlay(&mut self, file_path: &Path) {
+        self.overlays.remove(file_path);
+    }
+
+    fn read_source(&self, file_path: &Path) -> io::Result<String> {
+        match self.overlays.get(file_path) {
+            Some(source) => Ok(source.clone()),
+            None => fs::read_to_string(file_path),
+        }
+    }
+
+    pub fn add_file<FS: FileSystem>(&mut self, resolver: &Resolver<'_, FS>, file_path: &Path) {
+        let source = self.read_source(file_path);
+        let facts = FileFacts::collect(resolver, file_path, source, &self.resolve_options);
+        self.add_facts(facts);
+    }
+
+    // Analyzes `files` on the rayon thread pool, each with the resolver of its package. The facts
+    // are merged in the order of `files`, so the results don't depend on the number of threads.
+    pub fn add_files<FS: FileSystem + Sync>(&mut self, files: &[(&Resolver<'_, FS>, PathBuf)]) {
+        let facts: Vec<FileFacts> = files
+            .par_iter()
+            .map(|(resolver, file_path)| {
+                let source = self.read_source(file_path);
+                FileFacts::collect(resolver, file_path, source, &self.resolve_options)
+            })
+            .collect();
+        for facts in facts {
+            self.add_facts(facts);
+        }
+    }
+
+    // Like `add_files`, but reuses the facts in `cache` of files that haven't changed, and stores
+    // the facts of the others there.
+    pub fn add_files_cached<FS: FileSystem + Sync>(
+        &mut self,
+        files: &[(&Resolver<'_, FS>, PathBuf)],
+        cache: &mut FactsCache,
+    ) {
+        let lookups: Vec<_> = files
+            .par_iter()
+            .map(|(resolver, file_path)| {
+                let source = self.read_source(file_path);
+                cache.lookup(resolver, file_path, source, &self.resolve_options)
+            })
+            .collect();
+        for ((_, file_path), lookup) in files.iter().zip(lookups) {
+            let facts = cache.store(file_path, lookup);
+            self.add_facts(facts);
+        }
+    }
+
+    // Replaces the facts of the file, if it was added before.
+    fn add_facts(&mut self, facts: FileFacts) {
+        let file = facts.filename;
+        self.diagnostics.insert(file.clone(), facts.diagnostics);
+        self.unresolved_imports
+            .insert(file.clone(), facts.unresolved_imports);
+        let dependencies = facts.resolutions.into_iter();
+        self.dependencies.insert(
+            file.clone(),
+            dependencies
+                .map(|resolution| resolution.dependencies)
+                .collect(),
+        );
+        self.suppressions.insert(file.clone(), facts.suppressions);
+        match facts.modules {
+            Some((imports, exports)) => {
+                self.imports.insert(file.clone(), imports);
+                self.exports.insert(file, exports);
+            }
+            None => {
+                self.imports.remove(&file);
+                self.exports.remove(&file);
+            }
+        }
+    }
+
+    // Forgets everything about `file_path`, e.g. because it was deleted, or before it's added
+    // again: its facts, its diagnostics and whether it's an entry point or a test.
+    pub fn remove_file(&mut self, file_path: &Path) {
+        self.exports.remove(file_path);
+        self.imports.remove(file_path);
+        self.entry_points.remove(file_path);
+        self.test_files.remove(file_path);
+        self.suppressions.remove(file_path);
+        self.unresolved_imports.remove(file_path);
+        self.diagnostics.remove(file_path);
+        self.dependencies.remove(file_path);
+    }
+
+    // The added files whose imports may resolve differently since the `changed` files were
+    // created, changed or deleted, sorted. They have to be added again to find out.
+    pub fn dependents(&self, changed: &BTreeSet<PathBuf>) -> Vec<PathBuf> {
+        let mut dependents: Vec<PathBuf> = self
+            .dependencies
+            .iter()
+            .filter(|(_, dependencies)| {
+                dependencies
+                    .iter()
+                    .any(|dependencies| dependencies.is_affected_by(changed))
+            })
+            .map(|(file, _)| file.clone())
+            .collect();
+        dependents.sort();
+        dependents
+    }
+
+    // Marks a file as used from outside the analyzed code, e.g. a package's `main`. Once there are
+    // entry points, only code reachable from them counts as using anything.
+    pub fn add_entry_point(&mut self, file_path: &Path) {
+        self.entry_points.insert(file_path.into());
+    }
+
+    // Marks a file as a test. Exports that are only used by tests are reported as
+    // `test_only_exports` rather than as used.
+    pub fn add_test_file(&mut self, file_path: &Path) {
+        self.test_files.insert(file_path.into());
+    }
+
+    // Records a problem found outside of the analyzed files, e.g. with a package.json.
+    // `remove_file` of its file drops it.
+    pub fn add_diagnostic(&mut self, diagnostic: Diagnostic) {
+        let diagnostics = self.diagnostics.entry(diagnostic.file.clone()).or_default();
+        diagnostics.push(diagnostic);
+    }
+
+    // The findings in the files added so far. Files can still be added or removed afterwards.
+    pub fn finalize(&self) -> AnalysisResults {
+        let trace = self.trace_imports(true);
+        // Tracing again without the tests tells which exports only they use.
+        let production = match self.test_files.is_empty() {
+            true => None,
+            false => Some(self.trace_imports(false)),
+        };
+
+        let mut results = HashMap::new();
+        for (file, exports) in &self.exports {
+            let used = |trace: &Trace, name: &JsWord| {
+                trace.used.get(file).is_some_and(|used| used.contains(name))
+            };
+            let collect = |names: &HashMap<JsWord, JsWord>,
+                           live: &HashSet<(PathBuf, Owner)>,
+                           filter: &dyn Fn(&JsWord) -> bool| {
+                let mut collected: Vec<UnusedExport> = names
+                    .iter()
+                    .filter(|(exported_name, _)| filter(exported_name))
+                    .map(|(exported_name, original_name)| {
+                        let (location, end_location) = exports
+                            .locations
+                            .get(exported_name)
+                            .copied()
+                            .unwrap_or_default();
+                        UnusedExport {
+                            exported_name: exported_name.clone(),
+                            original_name: original_name.clone(),
+                            location,
+                            end_location,
+                            used_in_module: !exports.reexports.contains_key(exported_name)
+                                && !exports.namespace_reexports.contains_key(exported_name)
+                                && self.is_used_in_module(file, original_name, live),
+                        }
+                    })
+                    .collect();
+                collected.sort_by(|a, b| {
+                    (a.location, &*a.exported_name).cmp(&(b.location, &*b.exported_name))
+                });
+                collected
+            };
+            let unused = |names| collect(names, &trace.live, &|name| !used(&trace, name));
+
+            let mut test_only_exports = Vec::new();
+            if let Some(production) = &production {
+                if !self.test_files.contains(file) {
+                    let test_only = |name: &JsWord| used(&trace, name) && !used(production, name);
+                    for names in [&exports.exports, &exports.type_exports] {
+                        test_only_exports.extend(collect(names, &production.live, &test_only));
+                    }
+                    // Exports that production code uses in their own file are just exported for
+                    // the tests, which is fine.
+                    test_only_exports.retain(|export| !export.used_in_module);
+                    test_only_exports.sort_by(|a, b| {
+                        (a.location, &*a.exported_name).cmp(&(b.location, &*b.exported_name))
+                    });
                 }
             }
 
+            let module_results = ModuleResults {
+                unused_exports: unused(&exports.exports),
+                unused_type_exports: unused(&exports.type_exports),
+                test_only_exports,
+            };
+
             results.insert(file.into(), module_results);
         }
 
-        // Trace from the other side to resolve any star-imports (which may be chained)
-        for (filename, symbols) in &self.import_usage.imports {
-            for symbol in symbols {
-                //println!("checking symbol {}.{}", filename.display(), symbol);
-                match self.trace_export(filename.into(), &symbol) {
-                    Some(providing_module) => {
-                        //println!("traced symbol {}.{}", providing_module.display(), symbol);
-                        let module_results = results.get_mut(&providing_module).unwrap();
-                        module_results.unused_exports.remove(&symbol);
-                        module_results.unused_type_exports.remove(&symbol);
+        results.retain(|_, module_results| !module_results.is_empty());
+
+        let unused_files = self
+            .exports
+            .keys()
+            .filter(|file| !trace.loaded.contains(*file) && !self.entry_points.contains(*file))
+            .cloned()
+            .collect();
+
+        let mut diagnostics: Vec<_> = self.diagnostics.iter().collect();
+        diagnostics.sort_by_key(|(file, _)| *file);
+        let mut results = AnalysisResults {
+            modules: results,
+            unused_files,
+            missing_exports: trace.missing_exports,
+            unresolved_imports: self
+                .unresolved_imports
+                .values()
+                .flatten()
+                .cloned()
+                .collect(),
+            stale_suppressions: HashSet::new(),
+            diagnostics: diagnostics
+                .into_iter()
+                .flat_map(|(_, diagnostics)| diagnostics.iter().cloned())
+                .collect(),
+        };
+        self.suppress(&mut results);
+        results
+    }
+
+    // Drops the findings that comments suppress, and reports the suppression comments that don't
+    // suppress anything, so they don't outlive the code they were for. `PUBLIC_TAG` isn't
+    // reported, since it also documents the export.
+    fn suppress(&self, results: &mut AnalysisResults) {
+        // (file, location) of the comments that suppress something
+        let mut used = HashSet::new();
+        let mut suppressed = |file: &Path, line: Option<usize>| {
+            let Some(suppressions) = self.suppressions.get(file) else {
+                return false;
+            };
+            let comment = match (suppressions.disable, line) {
+                (Some(disable), _) => disable,
+                (None, Some(line)) => {
+                    let mut ignore = suppressions.ignore_next_line.iter();
+                    match ignore.find(|(_, next_line)| *next_line == line) {
+                        Some((location, _)) => *location,
+                        None => return false,
                     }
-                    None => continue, //panic!("symbol not found"),
+                }
+                (None, None) => return false,
+            };
+            used.insert((file.to_path_buf(), comment));
+            true
+        };
+
+        for (file, module_results) in results.modules.iter_mut() {
+            let exports = [
+                &mut module_results.unused_exports,
+                &mut module_results.unused_type_exports,
+                &mut module_results.test_only_exports,
+            ];
+            for exports in exports {
+                exports.retain(|export| !suppressed(file, Some(export.location.line)));
+            }
+        }
+        results
+            .modules
+            .retain(|_, module_results| !module_results.is_empty());
+        results.unused_files.retain(|file| !suppressed(file, None));
+        results
+            .missing_exports
+            .retain(|missing| !suppressed(&missing.importer, Some(missing.location.line)));
+        results
+            .unresolved_imports
+            .retain(|unresolved| !suppressed(&unresolved.importer, Some(unresolved.location.line)));
+
+        for (file, suppressions) in &self.suppressions {
+            let disable = suppressions.disable.map(|location| (DISABLE, location));
+            let ignore = suppressions.ignore_next_line.iter();
+            let comments = disable
+                .into_iter()
+                .chain(ignore.map(|(location, _)| (IGNORE_NEXT_LINE, *location)));
+            for (directive, location) in comments {
+                if !used.contains(&(file.clone(), location)) {
+                    results.stale_suppressions.insert(StaleSuppression {
+                        file: file.clone(),
+                        directive: directive.into(),
+                        location,
+                    });
                 }
             }
         }
+    }
 
-        results.retain(|_, module_results| {
-            !module_results.unused_exports.is_empty()
-                || !module_results.unused_type_exports.is_empty()
-        });
+    // Whether live code elsewhere in `file` references the declaration of `name`. References
+    // from within the declaration itself, e.g. recursive calls, don't count.
+    fn is_used_in_module(
+        &self,
+        file: &Path,
+        name: &JsWord,
+        live: &HashSet<(PathBuf, Owner)>,
+    ) -> bool {
+        let Some(imports) = self.imports.get(file) else {
+            return false;
+        };
+        let owner = imports.owners.get(name);
+        imports
+            .local_references
+            .iter()
+            .filter(|(referencing, _)| owner.is_none() || referencing.as_ref() != owner)
+            .filter(|(referencing, _)| live.contains(&(file.to_path_buf(), (*referencing).clone())))
+            .any(|(_, references)| {
+                references.iter().any(|reference| match owner {
+                    // e.g. `foo` for `export default function foo() {}`
+                    Some(owner) => imports.owners.get(reference) == Some(owner),
+                    None => reference == name,
+                })
+            })
+    }
 
-        results
+    // Trace every import to the module that provides it, which resolves any star-imports (which
+    // may be chained). Using a named re-export also uses the symbol it re-exports, so usage
+    // flows through barrel files. Using a namespace re-export only uses the members accessed on
+    // it, unless the namespace object is used as a whole.
+    //
+    // Usage only counts if the code it's in is live. Module-level code is live once the module
+    // is loaded, and a top-level declaration once it's exported and used or referenced from live
+    // code. Without entry points every module and declaration is live. Otherwise liveness
+    // spreads from the entry points, so whole chains of dead code are found in one run. Without
+    // `tests`, code in test files doesn't count as using anything.
+    fn trace_imports(&self, tests: bool) -> Trace {
+        let counts = |filename: &PathBuf| tests || !self.test_files.contains(filename);
+        let mut pending: Vec<PendingImport> = Vec::new();
+        let mut pending_owners: Vec<(PathBuf, Owner)> = Vec::new();
+        if self.entry_points.is_empty() {
+            for (filename, imports) in self.imports.iter().filter(|(filename, _)| counts(filename))
+            {
+                pending_owners.push((filename.clone(), None));
+                for owner in imports.owners.values() {
+                    pending_owners.push((filename.clone(), Some(owner.clone())));
+                }
+            }
+        } else {
+            for filename in self.entry_points.iter().filter(|filename| counts(filename)) {
+                pending_owners.push((filename.clone(), None));
+                // Whoever loads an entry point may use any of its exports.
+                for symbol in self.exported_names(filename, true, &mut HashSet::new()) {
+                    pending.push((filename.clone(), filename.clone(), symbol, Access::Whole));
+                }
+            }
+        }
+
+        let mut used: HashMap<PathBuf, HashSet<JsWord>> = HashMap::new();
+        let mut live = HashSet::new();
+        let mut loaded = HashSet::new();
+        // Public exports are used from outside the analyzed code, like those of entry points.
+        for (filename, suppressions) in &self.suppressions {
+            let public_exports = suppressions.public_exports.iter();
+            for symbol in public_exports
+                .filter(|symbol| self.trace_export(filename.clone(), symbol).is_some())
+            {
+                loaded.insert(filename.clone());
+                pending.push((
+                    filename.clone(),
+                    filename.clone(),
+                    symbol.clone(),
+                    Access::Whole,
+                ));
+            }
+        }
+        let mut missing = HashSet::new();
+        // Re-exports may form a cycle, so each symbol is only followed once.
+        let mut seen = HashSet::new();
+        loop {
+            if let Some((filename, owner)) = pending_owners.pop() {
+                let Some(imports) = self.imports.get(&filename) else {
+                    continue;
+                };
+                if !live.insert((filename.clone(), owner.clone())) {
+                    continue;
+                }
+                if let Some(usage) = imports.usage.get(&owner) {
+                    for dependency in &usage.dependencies {
+                        if *dependency != filename {
+                            loaded.insert(dependency.clone());
+                        }
+                    }
+                    self.push_usage(&filename, usage, &mut pending, &mut pending_owners);
+                }
+                if let Some(references) = imports.local_references.get(&owner) {
+                    for name in references {
+                        let owner = imports.owners.get(name).cloned();
+                        pending_owners.push((filename.clone(), owner));
+                    }
+                }
+                continue;
+            }
+
+            let Some((importer, filename, symbol, access)) = pending.pop() else {
+                break;
+            };
+            //println!("checking symbol {}.{}", filename.display(), symbol);
+            let providing_module = match self.trace_export(filename.clone(), &symbol) {
+                Some(providing_module) => providing_module,
+                None => {
+                    if self.has_known_exports(&filename, &mut HashSet::new()) {
+                        let imports = &self.imports[&importer];
+                        let location = imports
+                            .symbol_locations
+                            .get(&(filename.clone(), symbol.clone()))
+                            .or_else(|| imports.import_locations.get(&filename))
+                            .copied()
+                            .unwrap_or_default();
+                        missing.insert(MissingExport {
+                            importer,
+                            source: filename,
+                            symbol,
+                            location,
+                        });
+                    }
+                    continue;
+                }
+            };
+            //println!("traced symbol {}.{}", providing_module.display(), symbol);
+            if !seen.insert((providing_module.clone(), symbol.clone(), access.clone())) {
+                continue;
+            }
+
+            let exports = &self.exports[&providing_module];
+            if let Some((source, original_name)) = exports.reexports.get(&symbol) {
+                pending.push((
+                    providing_module.clone(),
+                    source.clone(),
+                    original_name.clone(),
+                    access.clone(),
+                ));
+            } else if let Some(source) = exports.namespace_reexports.get(&symbol) {
+                match access {
+                    Access::Named => {}
+                    Access::Member(ref member) => {
+                        pending.push((importer, source.clone(), member.clone(), Access::Whole))
+                    }
+                    Access::Whole => {
+                        for name in self.exported_names(source, false, &mut HashSet::new()) {
+                            pending.push((importer.clone(), source.clone(), name, Access::Whole));
+                        }
+                    }
+                }
+            } else if let Some(original_name) = exports
+                .exports
+                .get(&symbol)
+                .or_else(|| exports.type_exports.get(&symbol))
+            {
+                // The declaration behind the export is live.
+                let owner = self.imports[&providing_module].owners.get(original_name);
+                if let Some(owner) = owner {
+                    pending_owners.push((providing_module.clone(), Some(owner.clone())));
+                }
+            }
+            pending_owners.push((providing_module.clone(), None));
+
+            used.entry(providing_module).or_default().insert(symbol);
+        }
+
+        Trace {
+            used,
+            live,
+            loaded,
+            missing_exports: missing,
+        }
+    }
+
+    // Queues everything `usage` in `importer` uses.
+    fn push_usage(
+        &self,
+        importer: &Path,
+        usage: &ImportUsage,
+        pending: &mut Vec<PendingImport>,
+        pending_owners: &mut Vec<(PathBuf, Owner)>,
+    ) {
+        let importer = importer.to_path_buf();
+        for (filename, symbols) in &usage.named_imports {
+            for symbol in symbols {
+                let symbol = symbol.clone();
+                pending.push((importer.clone(), filename.clone(), symbol, Access::Named));
+            }
+        }
+        for (filename, symbols) in &usage.imports {
+            for symbol in symbols {
+                let symbol = symbol.clone();
+                pending.push((importer.clone(), filename.clone(), symbol, Access::Whole));
+            }
+        }
+        for (filename, symbols) in &usage.member_imports {
+            for (symbol, members) in symbols {
+                for member in members {
+                    let access = Access::Member(member.clone());
+                    pending.push((importer.clone(), filename.clone(), symbol.clone(), access));
+                }
+            }
+        }
+        for filename in &usage.escaped_modules {
+            for symbol in self.exported_names(filename, true, &mut HashSet::new()) {
+                pending.push((importer.clone(), filename.clone(), symbol, Access::Whole));
+            }
+        }
+        for filename in &usage.dependencies {
+            pending_owners.push((filename.clone(), None));
+        }
+    }
+
+    // Whether we know every name a module exports, i.e. it and every module it `export *`s from
+    // are analyzed and not CommonJS.
+    fn has_known_exports(&self, filename: &Path, visited: &mut HashSet<PathBuf>) -> bool {
+        if !visited.insert(filename.into()) {
+            return true;
+        }
+        match self.exports.get(filename) {
+            Some(exports) => {
+                !exports.commonjs
+                    && exports
+                        .export_alls
+                        .iter()
+                        .all(|export_all| self.has_known_exports(export_all, visited))
+            }
+            None => false,
+        }
     }
 
-    fn trace_export<'a>(&self, filename: PathBuf, symbol: &JsWord) -> Option<PathBuf> {
-        let exports = match self.exports.get(&filename) {
-            Some(exports) => exports,
-            None => return None,
-            //panic!("gah not found: {}", filename.display()),
+    // All names exported by a module, including those from `export *`. The namespace object of
+    // `export * as ns` never includes the default export of the re-exported module.
+    fn exported_names(
+        &self,
+        filename: &Path,
+        include_default: bool,
+        visited: &mut HashSet<PathBuf>,
+    ) -> Vec<JsWord> {
+        let exports = match self.exports.get(filename) {
+            Some(exports) if visited.insert(filename.into()) => exports,
+            _ => return Vec::new(),
         };
 
+        let mut names: Vec<JsWord> = exports
+            .exports
+            .keys()
+            .chain(exports.type_exports.keys())
+            .filter(|name| include_default || **name != *"default")
+            .cloned()
+            .collect();
+        for export_all in &exports.export_alls {
+            names.extend(self.exported_names(export_all, false, visited));
+        }
+        names
+    }
+
+    fn trace_export(&self, filename: PathBuf, symbol: &JsWord) -> Option<PathBuf> {
+        self.trace_export_from(filename, symbol, &mut HashSet::new())
+    }
+
+    fn trace_export_from(
+        &self,
+        filename: PathBuf,
+        symbol: &JsWord,
+        visited: &mut HashSet<PathBuf>,
+    ) -> Option<PathBuf> {
+        let exports = self.exports.get(&filename)?;
+
         if exports.exports.contains_key(symbol) || exports.type_exports.contains_key(symbol) {
             return Some(filename);
         }
 
+        // `export *` never re-exports the default export.
+        if *symbol == *"default" || !visited.insert(filename) {
+            return None;
+        }
+
         for export_all in exports.export_alls.iter().rev() {
-            let maybe_path = self.trace_export(export_all.into(), symbol);
+            let maybe_path = self.trace_export_from(export_all.into(), symbol, visited);
             if maybe_path.is_some() {
                 return maybe_path;
             }
@@ -770,11 +2643,16 @@ impl Analyzer {
 
 #[cfg(test)]
 mod tests {
+    use crate::fixture::TempDir;
     use crate::*;
     use parcel_resolver::OsFileSystem;
     use std::fs::canonicalize;
 
     fn analyze(filepaths: Vec<&str>) -> AnalysisResults {
+        analyze_from(filepaths, vec![])
+    }
+
+    fn analyze_from(filepaths: Vec<&str>, entry_points: Vec<&str>) -> AnalysisResults {
         let resolver = Resolver::parcel(
             PathBuf::from("testdata").into(),
             parcel_resolver::CacheCow::Owned(parcel_resolver::Cache::new(OsFileSystem)),
@@ -784,21 +2662,51 @@ mod tests {
             let path = canonicalize(filepath).unwrap();
             analyzer.add_file(&resolver, Path::new(&path));
         }
+        for entry_point in entry_points {
+            analyzer.add_entry_point(&path(entry_point));
+        }
         analyzer.finalize()
     }
 
     fn path(filename: &str) -> PathBuf {
-        canonicalize(filename).unwrap().into()
+        canonicalize(filename).unwrap()
+    }
+
+    // The original names of the unused exports, for comparing results without locations.
+    #[derive(Default, Debug, PartialEq)]
+    struct Unused {
+        unused_exports: HashSet<JsWord>,
+        unused_type_exports: HashSet<JsWord>,
+    }
+
+    fn unused(results: &AnalysisResults) -> HashMap<PathBuf, Unused> {
+        let names = |exports: &Vec<UnusedExport>| {
+            exports
+                .iter()
+                .map(|export| export.original_name.clone())
+                .collect()
+        };
+        results
+            .modules
+            .iter()
+            .map(|(file, module_results)| {
+                let unused = Unused {
+                    unused_exports: names(&module_results.unused_exports),
+                    unused_type_exports: names(&module_results.unused_type_exports),
+                };
+                (file.clone(), unused)
+            })
+            .collect()
     }
 
     #[test]
     fn named_exports() {
         let results = analyze(vec!["testdata/export_named.ts"]);
         assert_eq!(
-            results,
+            unused(&results),
             HashMap::from([(
                 path("testdata/export_named.ts"),
-                ModuleResults {
+                Unused {
                     unused_exports: HashSet::from([
                         "Class".into(),
                         "Enum".into(),
@@ -818,10 +2726,10 @@ mod tests {
     fn named_exports_inline() {
         let results = analyze(vec!["testdata/export_decl.ts"]);
         assert_eq!(
-            results,
+            unused(&results),
             HashMap::from([(
                 path("testdata/export_decl.ts"),
-                ModuleResults {
+                Unused {
                     unused_exports: HashSet::from([
                         "Class".into(),
                         "Enum".into(),
@@ -830,7 +2738,6 @@ mod tests {
                         "Const".into(),
                     ]),
                     unused_type_exports: HashSet::from(["Interface".into(), "Type".into(),]),
-                    ..Default::default()
                 }
             )])
         );
@@ -843,10 +2750,10 @@ mod tests {
             "testdata/import_named_partial_no_class.ts",
         ]);
         assert_eq!(
-            results,
+            unused(&results),
             HashMap::from([(
                 path("testdata/export_named.ts"),
-                ModuleResults {
+                Unused {
                     unused_exports: HashSet::from(["Class".into(),]),
                     ..Default::default()
                 }
@@ -861,17 +2768,17 @@ mod tests {
             "testdata/import_named_partial_no_class.ts",
             "testdata/import_named_partial_only_class.ts",
         ]);
-        assert_eq!(results, HashMap::new());
+        assert_eq!(unused(&results), HashMap::new());
     }
 
     #[test]
     fn aliased_named_exports() {
         let results = analyze(vec!["testdata/export_named_aliased.ts"]);
         assert_eq!(
-            results,
+            unused(&results),
             HashMap::from([(
                 path("testdata/export_named_aliased.ts"),
-                ModuleResults {
+                Unused {
                     // TODO(zbarsky): detect that Interface/Type are type exports
                     unused_exports: HashSet::from([
                         "Class".into(),
@@ -895,10 +2802,10 @@ mod tests {
             "testdata/import_named_aliased_no_enum.ts",
         ]);
         assert_eq!(
-            results,
+            unused(&results),
             HashMap::from([(
                 path("testdata/export_named_aliased.ts"),
-                ModuleResults {
+                Unused {
                     unused_exports: HashSet::from(["Enum".into(),]),
                     ..Default::default()
                 }
@@ -913,7 +2820,7 @@ mod tests {
             "testdata/import_named_aliased_no_enum.ts",
             "testdata/import_named_aliased_only_enum.ts",
         ]);
-        assert_eq!(results, HashMap::new());
+        assert_eq!(unused(&results), HashMap::new());
     }
 
     #[test]
@@ -923,10 +2830,10 @@ mod tests {
             "testdata/import_namespace_partial.ts",
         ]);
         assert_eq!(
-            results,
+            unused(&results),
             HashMap::from([(
                 path("testdata/export_named.ts"),
-                ModuleResults {
+                Unused {
                     unused_exports: HashSet::from(["Enum".into(),]),
                     ..Default::default()
                 }
@@ -934,6 +2841,46 @@ mod tests {
         );
     }
 
+    #[test]
+    fn namespace_import_shadowing() {
+        let results = analyze(vec![
+            "testdata/export_named.ts",
+            "testdata/import_namespace_shadowed.ts",
+        ]);
+        assert_eq!(
+            unused(&results),
+            HashMap::from([(
+                path("testdata/export_named.ts"),
+                Unused {
+                    unused_exports: HashSet::from([
+                        "Class".into(),
+                        "Fn".into(),
+                        "Type".into(),
+                        "Enum".into(),
+                        "Const".into(),
+                    ]),
+                    ..Default::default()
+                }
+            )])
+        );
+    }
+
+    #[test]
+    fn require_without_arguments() {
+        let results = analyze(vec!["testdata/require_without_arguments.ts"]);
+        assert!(results.diagnostics.is_empty());
+        assert_eq!(
+            unused(&results),
+            HashMap::from([(
+                path("testdata/require_without_arguments.ts"),
+                Unused {
+                    unused_exports: HashSet::from(["Value".into()]),
+                    ..Default::default()
+                }
+            )])
+        );
+    }
+
     #[test]
     fn require_named() {
         let results = analyze(vec![
@@ -941,10 +2888,10 @@ mod tests {
             "testdata/require_named.ts",
         ]);
         assert_eq!(
-            results,
+            unused(&results),
             HashMap::from([(
                 path("testdata/export_named.ts"),
-                ModuleResults {
+                Unused {
                     unused_exports: HashSet::from(["Class".into(),]),
                     ..Default::default()
                 }
@@ -959,10 +2906,10 @@ mod tests {
             "testdata/async_import_named.ts",
         ]);
         assert_eq!(
-            results,
+            unused(&results),
             HashMap::from([(
                 path("testdata/export_named.ts"),
-                ModuleResults {
+                Unused {
                     unused_exports: HashSet::from(["Class".into(),]),
                     ..Default::default()
                 }
@@ -980,10 +2927,10 @@ mod tests {
             "testdata/import_defaults.ts",
         ]);
         assert_eq!(
-            results,
+            unused(&results),
             HashMap::from([(
                 path("testdata/export_default_interface.ts"),
-                ModuleResults {
+                Unused {
                     unused_exports: HashSet::from(["default".into(),]),
                     ..Default::default()
                 }
@@ -999,18 +2946,18 @@ mod tests {
             "testdata/import_foo_bar.ts",
         ]);
         assert_eq!(
-            results,
+            unused(&results),
             HashMap::from([
                 (
                     path("testdata/export_foo.ts"),
-                    ModuleResults {
+                    Unused {
                         unused_exports: HashSet::from(["baz".into(),]),
                         ..Default::default()
                     }
                 ),
                 (
                     path("testdata/export_bar.ts"),
-                    ModuleResults {
+                    Unused {
                         unused_exports: HashSet::from(["foo".into(),]),
                         ..Default::default()
                     }
@@ -1022,7 +2969,7 @@ mod tests {
     #[test]
     fn acid_test() {
         let results = analyze(vec!["testdata/acid.ts"]);
-        assert_eq!(results, HashMap::from([]),);
+        assert_eq!(unused(&results), HashMap::from([]),);
     }
 
     #[test]
@@ -1035,18 +2982,18 @@ mod tests {
             "testdata/import_reexported.ts",
         ]);
         assert_eq!(
-            results,
+            unused(&results),
             HashMap::from([
                 (
                     path("testdata/export_foo.ts"),
-                    ModuleResults {
+                    Unused {
                         unused_exports: HashSet::from(["foo".into(), "bar".into(), "baz".into(),]),
                         ..Default::default()
                     }
                 ),
                 (
                     path("testdata/export_bar.ts"),
-                    ModuleResults {
+                    Unused {
                         unused_exports: HashSet::from(["baz".into(),]),
                         ..Default::default()
                     }
@@ -1054,4 +3001,613 @@ mod tests {
             ])
         );
     }
+
+    #[test]
+    fn export_named_from_test() {
+        let results = analyze(vec![
+            "testdata/export_foo.ts",
+            "testdata/export_default_class.ts",
+            "testdata/reexport_named.ts",
+            "testdata/reexport_named_again.ts",
+            "testdata/import_reexported_named.ts",
+        ]);
+        assert_eq!(
+            unused(&results),
+            HashMap::from([
+                (
+                    path("testdata/export_foo.ts"),
+                    Unused {
+                        unused_exports: HashSet::from(["bar".into(), "baz".into(),]),
+                        ..Default::default()
+                    }
+                ),
+                (
+                    path("testdata/reexport_named.ts"),
+                    Unused {
+                        unused_exports: HashSet::from(["bar".into(),]),
+                        ..Default::default()
+                    }
+                )
+            ])
+        );
+    }
+
+    #[test]
+    fn export_named_from_cycle_test() {
+        // Each barrel re-exports `cycle` from the other.
+        let results = analyze(vec![
+            "testdata/reexport_cycle_a.ts",
+            "testdata/reexport_cycle_b.ts",
+            "testdata/import_reexport_cycle.ts",
+        ]);
+        assert_eq!(
+            unused(&results),
+            HashMap::from([(
+                path("testdata/reexport_cycle_a.ts"),
+                Unused {
+                    unused_exports: HashSet::from(["notCycle".into()]),
+                    ..Default::default()
+                }
+            )])
+        );
+    }
+
+    #[test]
+    fn export_namespace_test() {
+        let results = analyze(vec![
+            "testdata/export_decl.ts",
+            "testdata/export_namespace.ts",
+            "testdata/import_namespace_reexport.ts",
+        ]);
+        assert_eq!(
+            unused(&results),
+            HashMap::from([(
+                path("testdata/export_decl.ts"),
+                Unused {
+                    unused_exports: HashSet::from(["Enum".into(), "Var".into(), "Const".into(),]),
+                    unused_type_exports: HashSet::from(["Type".into(),]),
+                }
+            )])
+        );
+    }
+
+    #[test]
+    fn export_namespace_used_whole_test() {
+        let results = analyze(vec![
+            "testdata/export_decl.ts",
+            "testdata/export_namespace.ts",
+            "testdata/import_namespace_reexport_whole.ts",
+        ]);
+        assert_eq!(unused(&results), HashMap::new());
+    }
+
+    #[test]
+    fn namespace_import_used_whole() {
+        let results = analyze(vec![
+            "testdata/export_named.ts",
+            "testdata/import_namespace_escaped.ts",
+        ]);
+        assert_eq!(unused(&results), HashMap::new());
+    }
+
+    const REACHABILITY_FILES: [&str; 5] = [
+        "testdata/reachability_entry.ts",
+        "testdata/reachability_lib.ts",
+        "testdata/reachability_helper.ts",
+        "testdata/reachability_side_effect.ts",
+        "testdata/reachability_orphan.ts",
+    ];
+
+    #[test]
+    fn without_entry_points() {
+        let results = analyze(REACHABILITY_FILES.to_vec());
+        assert_eq!(
+            unused(&results),
+            HashMap::from([
+                (
+                    path("testdata/reachability_entry.ts"),
+                    Unused {
+                        unused_exports: HashSet::from(["api".into()]),
+                        ..Default::default()
+                    }
+                ),
+                (
+                    path("testdata/reachability_lib.ts"),
+                    Unused {
+                        unused_exports: HashSet::from(["unused".into()]),
+                        ..Default::default()
+                    }
+                ),
+                (
+                    path("testdata/reachability_orphan.ts"),
+                    Unused {
+                        unused_exports: HashSet::from(["orphan".into()]),
+                        ..Default::default()
+                    }
+                ),
+            ])
+        );
+    }
+
+    #[test]
+    fn entry_points_reachability() {
+        let results = analyze_from(
+            REACHABILITY_FILES.to_vec(),
+            vec!["testdata/reachability_entry.ts"],
+        );
+        assert_eq!(
+            unused(&results),
+            HashMap::from([
+                (
+                    path("testdata/reachability_lib.ts"),
+                    Unused {
+                        unused_exports: HashSet::from(["unused".into()]),
+                        ..Default::default()
+                    }
+                ),
+                (
+                    path("testdata/reachability_helper.ts"),
+                    Unused {
+                        unused_exports: HashSet::from(["deadHelper".into(), "orphanHelper".into()]),
+                        ..Default::default()
+                    }
+                ),
+                (
+                    path("testdata/reachability_orphan.ts"),
+                    Unused {
+                        unused_exports: HashSet::from(["orphan".into()]),
+                        ..Default::default()
+                    }
+                ),
+            ])
+        );
+    }
+
+    #[test]
+    fn package_entry_points() {
+        assert_eq!(
+            entry_points::package_entry_points(Path::new("testdata/package_entry")).unwrap(),
+            vec![
+                path("testdata/package_entry/bin/cli.ts"),
+                path("testdata/package_entry/src/index.ts"),
+                path("testdata/package_entry/src/utils.ts"),
+            ]
+        );
+    }
+
+    #[test]
+    fn unused_files() {
+        let results = analyze(REACHABILITY_FILES.to_vec());
+        assert_eq!(
+            results.unused_files,
+            HashSet::from([
+                path("testdata/reachability_entry.ts"),
+                path("testdata/reachability_orphan.ts"),
+            ])
+        );
+
+        let results = analyze_from(
+            REACHABILITY_FILES.to_vec(),
+            vec!["testdata/reachability_entry.ts"],
+        );
+        assert_eq!(
+            results.unused_files,
+            HashSet::from([path("testdata/reachability_orphan.ts")])
+        );
+    }
+
+    #[test]
+    fn unused_files_dynamic_imports() {
+        let results = analyze(vec![
+            "testdata/export_named.ts",
+            "testdata/async_import_named.ts",
+            "testdata/require_named.ts",
+        ]);
+        assert_eq!(
+            results.unused_files,
+            HashSet::from([
+                path("testdata/async_import_named.ts"),
+                path("testdata/require_named.ts"),
+            ])
+        );
+    }
+
+    #[test]
+    fn missing_exports() {
+        // export_foo.ts isn't analyzed and commonjs_exports.js may export anything.
+        let results = analyze(vec![
+            "testdata/export_named.ts",
+            "testdata/commonjs_exports.js",
+            "testdata/reexport_missing.ts",
+            "testdata/import_missing.ts",
+        ]);
+        assert_eq!(
+            results.missing_exports,
+            HashSet::from([
+                MissingExport {
+                    importer: path("testdata/import_missing.ts"),
+                    source: path("testdata/export_named.ts"),
+                    symbol: "Missing".into(),
+                    location: Location {
+                        line: 1,
+                        column: 17
+                    },
+                },
+                MissingExport {
+                    importer: path("testdata/import_missing.ts"),
+                    source: path("testdata/export_named.ts"),
+                    symbol: "AlsoMissing".into(),
+                    location: Location { line: 9, column: 7 },
+                },
+                MissingExport {
+                    importer: path("testdata/reexport_missing.ts"),
+                    source: path("testdata/export_named.ts"),
+                    symbol: "Gone".into(),
+                    location: Location {
+                        line: 1,
+                        column: 10
+                    },
+                },
+            ])
+        );
+    }
+
+    #[test]
+    fn unused_export_locations() {
+        let results = analyze(vec!["testdata/export_named_aliased.ts"]);
+        let unused_export = |exported_name: &str, original_name: &str, line, column| UnusedExport {
+            exported_name: exported_name.into(),
+            original_name: original_name.into(),
+            location: Location { line, column },
+            end_location: Location {
+                line,
+                column: column + exported_name.len(),
+            },
+            used_in_module: false,
+        };
+        assert_eq!(
+            results.modules[&path("testdata/export_named_aliased.ts")],
+            ModuleResults {
+                unused_exports: vec![
+                    unused_export("AliasedClass", "Class", 11, 12),
+                    unused_export("AliasedFn", "Fn", 12, 9),
+                    unused_export("AliasedConst", "Const", 13, 12),
+                    unused_export("AliasedVar", "Var", 14, 10),
+                    unused_export("AliasedInterface", "Interface", 15, 16),
+                    unused_export("AliasedType", "Type", 16, 11),
+                    unused_export("AliasedEnum", "Enum", 17, 11),
+                ],
+                unused_type_exports: vec![],
+                test_only_exports: vec![],
+            }
+        );
+    }
+
+    #[test]
+    fn used_in_module() {
+        let results = analyze(vec!["testdata/export_used_locally.ts"]);
+        let module_results = &results.modules[&path("testdata/export_used_locally.ts")];
+        let used_in_module: HashMap<JsWord, bool> = module_results
+            .unused_exports
+            .iter()
+            .chain(&module_results.unused_type_exports)
+            .map(|export| (export.exported_name.clone(), export.used_in_module))
+            .collect();
+        assert_eq!(
+            used_in_module,
+            HashMap::from([
+                ("helper".into(), true),
+                ("recursive".into(), false),
+                ("value".into(), true),
+                ("default".into(), true),
+                ("Options".into(), true),
+            ])
+        );
+
+        // They're reported, so they count.
+        let report = report::Report::new(&results);
+        let count = |kind| report.count(kind);
+        assert_eq!(
+            count(FindingKind::UnusedExport) + count(FindingKind::UnusedTypeExport),
+            5
+        );
+    }
+
+    #[test]
+    fn json_report() {
+        // parse_error.ts can't be analyzed, but doesn't keep the rest from being analyzed.
+        let results = analyze(vec![
+            "testdata/import_unresolved.ts",
+            "testdata/parse_error.ts",
+        ]);
+        let report = report::Report::new(&results);
+        assert_eq!(report.count(report::FindingKind::UnusedExport), 1);
+        assert_eq!(report.count(report::FindingKind::UnresolvedImport), 1);
+        let json: serde_json::Value = serde_json::from_str(&report.to_json().unwrap()).unwrap();
+        let file = path("testdata/import_unresolved.ts");
+        assert_eq!(
+            json,
+            serde_json::json!({
+                "version": report::REPORT_VERSION,
+                "unused_exports": [{
+                    "file": file,
+                    "exported_name": "value",
+                    "original_name": "value",
+                    "location": { "line": 3, "column": 14 },
+                    "end_location": { "line": 3, "column": 19 },
+                    "used_in_module": false,
+                }],
+                "unused_type_exports": [],
+                "test_only_exports": [],
+                "unused_files": [file],
+                "missing_exports": [],
+                "unresolved_imports": [{
+                    "importer": file,
+                    "specifier": "./does_not_exist",
+                    "location": { "line": 1, "column": 1 },
+                }],
+                "stale_suppressions": [],
+                "diagnostics": [{
+                    "severity": "error",
+                    "file": path("testdata/parse_error.ts"),
+                    "message": results.diagnostics[0].message,
+                }],
+                "fixed_baseline_entries": [],
+            })
+        );
+        assert!(results.diagnostics[0]
+            .message
+            .starts_with("failed to parse"));
+    }
+
+    #[test]
+    fn fix_unused_exports() {
+        let results = analyze(vec!["testdata/fix_exports.ts", "testdata/fix_importer.ts"]);
+        let file = path("testdata/fix_exports.ts");
+        let source = std::fs::read_to_string(&file).unwrap();
+        let fixed = fix::fix_source(&source, &results.modules[&file]).unwrap();
+        assert_eq!(
+            fixed,
+            r#"export const a = 1;
+export const [, B] = [1, 2];
+export const { O2 } = { O1: 1, O2: 2 };
+function helper() {}
+helper();
+const store = createStore();
+export const keep = 1;
+const spec1 = 1, spec2 = 2, spec3 = 3;
+export {
+  spec2,
+};
+declare function createStore(): number;
+"#
+        );
+    }
+
+    #[test]
+    fn baseline() {
+        let root = path("testdata");
+        let before = analyze(vec!["testdata/fix_exports.ts"]);
+        let baseline = baseline::Baseline::new(&before, &root);
+        assert!(baseline.entries.contains(&baseline::BaselineEntry {
+            file: "fix_exports.ts".into(),
+            symbol: "keep".into(),
+            kind: "unused-export".into(),
+        }));
+
+        // Importing the file fixes the entries of it and the exports used.
+        let mut after = analyze(vec!["testdata/fix_exports.ts", "testdata/fix_importer.ts"]);
+        let fixed = baseline.apply(&mut after, &root);
+        let fixed: Vec<(&str, &str)> = fixed
+            .iter()
+            .map(|entry| (&*entry.symbol, &*entry.kind))
+            .collect();
+        assert_eq!(
+            fixed,
+            vec![
+                ("", "unused-file"),
+                ("B", "unused-export"),
+                ("O2", "unused-export"),
+                ("a", "unused-export"),
+                ("keep", "unused-export"),
+                ("spec2", "unused-export"),
+            ]
+        );
+        // Only the importer, which nothing loads, is new.
+        assert!(after.modules.is_empty());
+        assert_eq!(
+            after.unused_files,
+            HashSet::from([path("testdata/fix_importer.ts")])
+        );
+    }
+
+    #[test]
+    fn parallel_analysis() {
+        let resolver = Resolver::parcel(
+            PathBuf::from("testdata").into(),
+            parcel_resolver::CacheCow::Owned(parcel_resolver::Cache::new(OsFileSystem)),
+        );
+        let files: Vec<_> = std::fs::read_dir("testdata")
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .filter(|file| file.is_file())
+            .map(|file| (&resolver, canonicalize(file).unwrap()))
+            .collect();
+        let analyze_on = |threads| {
+            let pool = rayon::ThreadPoolBuilder::new()
+                .num_threads(threads)
+                .build()
+                .unwrap();
+            pool.install(|| {
+                let mut analyzer = Analyzer::new(Default::default());
+                analyzer.add_files(&files);
+                analyzer.finalize()
+            })
+        };
+        let sequential = analyze_on(1);
+        assert!(!sequential.modules.is_empty());
+        assert_eq!(sequential, analyze_on(4));
+    }
+
+    #[test]
+    fn suppressions() {
+        let results = analyze(vec![
+            "testdata/suppressions.ts",
+            "testdata/suppressions_disabled.ts",
+        ]);
+        assert_eq!(
+            unused(&results),
+            HashMap::from([(
+                path("testdata/suppressions.ts"),
+                Unused {
+                    unused_exports: HashSet::from(["Reported".into()]),
+                    ..Default::default()
+                }
+            )])
+        );
+        // The public export uses the file, the other is disabled.
+        assert!(results.unused_files.is_empty());
+        assert!(results.unresolved_imports.is_empty());
+        assert_eq!(
+            results.stale_suppressions,
+            HashSet::from([StaleSuppression {
+                file: path("testdata/suppressions.ts"),
+                directive: IGNORE_NEXT_LINE.into(),
+                location: Location {
+                    line: 14,
+                    column: 1
+                },
+            }])
+        );
+    }
+
+    #[test]
+    fn test_only_exports() {
+        let dir = TempDir::new("tests");
+        dir.write("package.json", "{}");
+        dir.write(
+            "index.ts",
+            "import { used } from './lib';\nconsole.log(used);\n",
+        );
+        dir.write(
+            "lib.ts",
+            "export const used = 1;\nexport const tested = 2;\nexport const unused = 3;\n",
+        );
+        dir.write(
+            "__tests__/lib.ts",
+            "import { used, tested } from '../lib';\nconsole.log(used, tested);\n",
+        );
+
+        let resolver = dir.resolver("");
+        let mut analyzer = Analyzer::new(Default::default());
+        for file in ["index.ts", "lib.ts", "__tests__/lib.ts"] {
+            analyzer.add_file(&resolver, &dir.join(file));
+        }
+        analyzer.add_entry_point(&dir.join("index.ts"));
+        analyzer.add_entry_point(&dir.join("__tests__/lib.ts"));
+        analyzer.add_test_file(&dir.join("__tests__/lib.ts"));
+        let results = analyzer.finalize();
+
+        let lib = &results.modules[&dir.join("lib.ts")];
+        let names = |exports: &[UnusedExport]| -> Vec<String> {
+            exports
+                .iter()
+                .map(|export| export.exported_name.to_string())
+                .collect()
+        };
+        assert_eq!(names(&lib.unused_exports), vec!["unused"]);
+        assert_eq!(names(&lib.test_only_exports), vec!["tested"]);
+        assert!(!results.modules.contains_key(&dir.join("__tests__/lib.ts")));
+    }
+
+    #[test]
+    fn shared_resolver_cache() {
+        let cache = parcel_resolver::Cache::new(OsFileSystem);
+        let resolver = || {
+            Resolver::parcel(
+                PathBuf::from("testdata").into(),
+                parcel_resolver::CacheCow::Borrowed(&cache),
+            )
+        };
+        let (first, second) = (resolver(), resolver());
+        let mut analyzer = Analyzer::new(Default::default());
+        analyzer.add_file(&first, &path("testdata/import_named_partial_only_class.ts"));
+        let before = cache.stats();
+        assert!(before.misses > 0);
+
+        // Resolving the same import from another resolver only hits the cache.
+        analyzer.add_file(&second, &path("testdata/import_named_partial_no_class.ts"));
+        let after = cache.stats();
+        assert!(after.hits > before.hits);
+        assert_eq!(after.misses, before.misses);
+    }
+
+    #[test]
+    fn sarif_report() {
+        let results = analyze(vec!["testdata/import_unresolved.ts"]);
+        let sarif = sarif::to_sarif(&report::Report::new(&results), &path("testdata"));
+        let run = &sarif["runs"][0];
+        let rule_ids: Vec<&str> = run["results"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|result| result["ruleId"].as_str().unwrap())
+            .collect();
+        assert_eq!(
+            rule_ids,
+            vec!["unused-export", "unused-file", "unresolved-import"]
+        );
+
+        let unresolved = &run["results"][2];
+        assert_eq!(
+            unresolved["locations"][0]["physicalLocation"],
+            serde_json::json!({
+                "artifactLocation": { "uri": "import_unresolved.ts", "uriBaseId": "SRCROOT" },
+                "region": { "startLine": 1, "startColumn": 1 },
+            })
+        );
+        assert_eq!(run["tool"]["driver"]["rules"][5]["id"], "unresolved-import");
+        assert_eq!(run["invocations"][0]["executionSuccessful"], true);
+        let failed = analyze(vec!["testdata/parse_error.ts"]);
+        let sarif = sarif::to_sarif(&report::Report::new(&failed), &path("testdata"));
+        assert_eq!(
+            sarif["runs"][0]["invocations"][0]["executionSuccessful"],
+            false
+        );
+
+        // Fingerprints are deterministic and differ between findings.
+        let again = sarif::to_sarif(&report::Report::new(&results), &path("testdata"));
+        assert_eq!(
+            run["results"][0]["partialFingerprints"],
+            again["runs"][0]["results"][0]["partialFingerprints"]
+        );
+        assert_ne!(
+            run["results"][0]["partialFingerprints"],
+            run["results"][1]["partialFingerprints"]
+        );
+
+        let results = AnalysisResults {
+            unused_files: HashSet::from(["/my repo/src/a#1.ts".into(), "/elsewhere/b.ts".into()]),
+            ..Default::default()
+        };
+        let sarif = sarif::to_sarif(&report::Report::new(&results), Path::new("/my repo"));
+        let run = &sarif["runs"][0];
+        assert_eq!(
+            run["originalUriBaseIds"]["SRCROOT"]["uri"],
+            "file:///my%20repo/"
+        );
+        let artifacts: Vec<_> = run["results"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|result| &result["locations"][0]["physicalLocation"]["artifactLocation"])
+            .collect();
+        assert_eq!(
+            artifacts,
+            [
+                &serde_json::json!({ "uri": "file:///elsewhere/b.ts" }),
+                &serde_json::json!({ "uri": "src/a%231.ts", "uriBaseId": "SRCROOT" }),
+            ]
+        );
+    }
 }
@@ -0,0 +1,768 @@
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::fs;
+use std::io::{self, BufRead, Write};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, RecvTimeoutError, Sender};
+use std::thread;
+use std::time::Duration;
+
+use serde_json::{json, Value};
+use url::Url;
+
+use crate::fix::fix_source;
+use crate::{AnalysisResults, Location, ModuleResults, UnusedExport};
+
+const METHOD_NOT_FOUND: i64 = -32601;
+const TEXT_DOCUMENT_SYNC_FULL: u8 = 1;
+const SEVERITY_HINT: u8 = 4;
+const TAG_UNNECESSARY: u8 = 1;
+const MESSAGE_TYPE_ERROR: u8 = 1;
+
+// Speaks the Language Server Protocol over `input` and `output` until the client exits. Unused
+// exports are published as hints, with code actions to remove them.
+//
+// `analyze` analyzes the repo at the given root, with the given contents of open documents
+// instead of the files on disk. It's called once documents or files changed and no message came
+// for `debounce`, e.g. because the user stopped typing, with the files that changed since the
+// last call. So it should only analyze what the changes affect.
+pub fn serve<F>(
+    input: impl BufRead + Send + 'static,
+    output: impl Write,
+    debounce: Duration,
+    analyze: F,
+) -> Result<(), String>
+where
+    F: FnMut(
+        &Path,
+        &HashMap<PathBuf, String>,
+        &BTreeSet<PathBuf>,
+    ) -> Result<AnalysisResults, String>,
+{
+    // Read on another thread, so we can wait for the next message and the debounce at once.
+    let (sender, receiver) = mpsc::channel();
+    thread::spawn(move || read_messages(input, sender));
+    let mut server = Server {
+        output,
+        analyze,
+        root: None,
+        documents: HashMap::new(),
+        stale: false,
+        changed: BTreeSet::new(),
+        results: AnalysisResults::default(),
+        published: HashSet::new(),
+    };
+    loop {
+        let received = match server.stale {
+            true => match receiver.recv_timeout(debounce) {
+                Ok(received) => received,
+                Err(RecvTimeoutError::Timeout) => {
+                    server.analyze()?;
+                    continue;
+                }
+                Err(RecvTimeoutError::Disconnected) => break,
+            },
+            false => match receiver.recv() {
+                Ok(received) => received,
+                Err(_) => break,
+            },
+        };
+        let message = received.map_err(|err| err.to_string())?;
+        let params = &message["params"];
+        match (message["method"].as_str(), message.get("id")) {
+            (Some(method), Some(id)) => {
+                // Code actions come from the results, so they have to be up to date.
+                if method == "textDocument/codeAction" {
+                    server.analyze()?;
+                }
+                let response = match server.request(method, params) {
+                    Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+                    Err((code, message)) => json!({
+                        "jsonrpc": "2.0",
+                        "id": id,
+                        "error": { "code": code, "message": message },
+                    }),
+                };
+                server.send(&response)?;
+            }
+            (Some("exit"), None) => break,
+            (Some(method), None) => server.notification(method, params),
+            // Responses, but we don't send requests.
+            (None, _) => {}
+        }
+    }
+    Ok(())
+}
+
+struct Server<W, F> {
+    output: W,
+    analyze: F,
+    root: Option<PathBuf>,
+    // file -> contents of the open documents
+    documents: HashMap<PathBuf, String>,
+    // Whether there are changes that haven't been analyzed yet
+    stale: bool,
+    // The documents and files that changed since the last analysis
+    changed: BTreeSet<PathBuf>,
+    results: AnalysisResults,
+    // Files with diagnostics, which have to be cleared once there are none
+    published: HashSet<PathBuf>,
+}
+
+impl<W, F> Server<W, F>
+where
+    W: Write,
+    F: FnMut(
+        &Path,
+        &HashMap<PathBuf, String>,
+        &BTreeSet<PathBuf>,
+    ) -> Result<AnalysisResults, String>,
+{
+    fn request(&mut self, method: &str, params: &Value) -> Result<Value, (i64, String)> {
+        match method {
+            "initialize" => {
+                let root = params["rootUri"]
+                    .as_str()
+                    .and_then(uri_to_path)
+                    .or_else(|| params["rootPath"].as_str().map(canonical_path));
+                self.root = root;
+                Ok(json!({
+                    "capabilities": {
+                        "textDocumentSync": {
+                            "openClose": true,
+                            "change": TEXT_DOCUMENT_SYNC_FULL,
+                            "save": true,
+                        },
+                        "codeActionProvider": true,
+                    },
+                    "serverInfo": {
+                        "name": env!("CARGO_PKG_NAME"),
+                        "version": env!("CARGO_PKG_VERSION"),
+                    },
+                }))
+            }
+            "shutdown" => Ok(Value::Null),
+            "textDocument/codeAction" => Ok(self.code_actions(params)),
+            _ => Err((METHOD_NOT_FOUND, format!("unhandled method {}", method))),
+        }
+    }
+
+    // Records the changes, which are analyzed once no message came for a while.
+    fn notification(&mut self, method: &str, params: &Value) {
+        // Documents and files outside the repo aren't analyzed.
+        let root = self.root.clone();
+        let in_root = |uri: &Value| {
+            let file = uri.as_str().and_then(uri_to_path)?;
+            root.as_ref()
+                .is_some_and(|root| file.starts_with(root))
+                .then_some(file)
+        };
+        let file = in_root(&params["textDocument"]["uri"]);
+        match (method, file) {
+            ("initialized", _) => {}
+            ("textDocument/didOpen", Some(file)) => {
+                let text = params["textDocument"]["text"].as_str().unwrap_or_default();
+                self.documents.insert(file.clone(), text.into());
+                self.changed.insert(file);
+            }
+            // Only full changes are requested, so the last one has the whole text.
+            ("textDocument/didChange", Some(file)) => {
+                let changes = params["contentChanges"].as_array();
+                if let Some(text) = changes.and_then(|changes| changes.last()?["text"].as_str()) {
+                    self.documents.insert(file.clone(), text.into());
+                    self.changed.insert(file);
+                }
+            }
+            ("textDocument/didClose", Some(file)) => {
+                self.documents.remove(&file);
+                self.changed.insert(file);
+            }
+            // The file on disk changed, e.g. for the files that import it.
+            ("textDocument/didSave", Some(file)) => {
+                self.changed.insert(file);
+            }
+            ("workspace/didChangeWatchedFiles", _) => {
+                let changes = params["changes"].as_array().into_iter().flatten();
+                self.changed
+                    .extend(changes.filter_map(|change| in_root(&change["uri"])));
+            }
+            _ => return,
+        }
+        self.stale = true;
+    }
+
+    // Analyzes the changes since the last analysis, if there are any, and publishes the results.
+    fn analyze(&mut self) -> Result<(), String> {
+        if !self.stale {
+            return Ok(());
+        }
+        self.stale = false;
+        let Some(root) = &self.root else {
+            return Ok(());
+        };
+        let changed = std::mem::take(&mut self.changed);
+        match (self.analyze)(root, &self.documents, &changed) {
+            Ok(results) => self.results = results,
+            Err(message) => {
+                let params = json!({ "type": MESSAGE_TYPE_ERROR, "message": message });
+                return self.notify("window/showMessage", params);
+            }
+        }
+
+        let mut files: Vec<(PathBuf, Vec<Value>)> = self
+            .results
+            .modules
+            .iter()
+            .map(|(file, module_results)| {
+                let text = self.text(file);
+                let test_only = module_results.test_only_exports.iter();
+                let diagnostics = exports(module_results)
+                    .map(|export| diagnostic(&text, export))
+                    .chain(test_only.map(|export| test_only_diagnostic(&text, export)))
+                    .collect();
+                (file.clone(), diagnostics)
+            })
+            .collect();
+        files.sort_by(|a, b| a.0.cmp(&b.0));
+        let mut published = HashSet::new();
+        for (file, diagnostics) in files {
+            self.publish(&file, diagnostics)?;
+            published.insert(file);
+        }
+        let mut fixed: Vec<PathBuf> = self.published.difference(&published).cloned().collect();
+        fixed.sort();
+        for file in &fixed {
+            self.publish(file, Vec::new())?;
+        }
+        self.published = published;
+        Ok(())
+    }
+
+    fn publish(&mut self, file: &Path, diagnostics: Vec<Value>) -> Result<(), String> {
+        let Some(uri) = path_to_uri(file) else {
+            return Ok(());
+        };
+        let params = json!({ "uri": uri, "diagnostics": diagnostics });
+        self.notify("textDocument/publishDiagnostics", params)
+    }
+
+    // An action for each unused export in the requested range, and one to remove all of the
+    // file's unused exports if there are several.
+    fn code_actions(&self, params: &Value) -> Value {
+        let uri = &params["textDocument"]["uri"];
+        let Some(file) = uri.as_str().and_then(uri_to_path) else {
+            return json!([]);
+        };
+        let Some(module_results) = self.results.modules.get(&file) else {
+            return json!([]);
+        };
+        let text = self.text(&file);
+        let start = position_key(&params["range"]["start"]);
+        let end = position_key(&params["range"]["end"]);
+
+        let mut actions = Vec::new();
+        for export in exports(module_results) {
+            let diagnostic = diagnostic(&text, export);
+            let range = &diagnostic["range"];
+            if position_key(&range["end"]) < start || position_key(&range["start"]) > end {
+                continue;
+            }
+            let title = match export.used_in_module {
+                false => format!(
+                    "Remove unused export {:?}",
+                    export.exported_name.to_string()
+                ),
+                true => format!(
+                    "Remove unnecessary export keyword of {:?}",
+                    export.exported_name.to_string()
+                ),
+            };
+            let fix = ModuleResults {
+                unused_exports: vec![export.clone()],
+                ..Default::default()
+            };
+            if let Some(action) = code_action(uri, &text, &fix, title, vec![diagnostic]) {
+                actions.push(action);
+            }
+        }
+        if exports(module_results).count() > 1 {
+            let title = "Remove all unused exports in this file".into();
+            if let Some(action) = code_action(uri, &text, module_results, title, Vec::new()) {
+                actions.push(action);
+            }
+        }
+        Value::Array(actions)
+    }
+
+    // The contents of `file`, from the open document if there's one.
+    fn text(&self, file: &Path) -> String {
+        match self.documents.get(file) {
+            Some(text) => text.clone(),
+            None => fs::read_to_string(file).unwrap_or_default(),
+        }
+    }
+
+    fn notify(&mut self, method: &str, params: Value) -> Result<(), String> {
+        self.send(&json!({ "jsonrpc": "2.0", "method": method, "params": params }))
+    }
+
+    fn send(&mut self, message: &Value) -> Result<(), String> {
+        let body = message.to_string();
+        write!(
+            self.output,
+            "Content-Length: {}\r\n\r\n{}",
+            body.len(),
+            body
+        )
+        .and_then(|()| self.output.flush())
+        .map_err(|err| err.to_string())
+    }
+}
+
+// Sends the messages in `input` until the client exits, the input ends or can't be read.
+fn read_messages(mut input: impl BufRead, sender: Sender<io::Result<Value>>) {
+    while let Some(received) = read_message(&mut input).transpose() {
+        let last = match &received {
+            Ok(message) => message["method"] == "exit",
+            Err(_) => true,
+        };
+        if sender.send(received).is_err() || last {
+            break;
+        }
+    }
+}
+
+fn read_message(input: &mut impl BufRead) -> io::Result<Option<Value>> {
+    let mut length = None;
+    loop {
+        let mut line = String::new();
+        if input.read_line(&mut line)? == 0 {
+            return Ok(None);
+        }
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            if name.eq_ignore_ascii_case("Content-Length") {
+                length = value.trim().parse().ok();
+            }
+        }
+    }
+    let length = length
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "missing Content-Length"))?;
+    let mut body = vec![0; length];
+    input.read_exact(&mut body)?;
+    let message = serde_json::from_slice(&body)
+        .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+    Ok(Some(message))
+}
+
+fn exports(module_results: &ModuleResults) -> impl Iterator<Item = &UnusedExport> {
+    module_results
+        .unused_exports
+        .iter()
+        .chain(&module_results.unused_type_exports)
+}
+
+fn diagnostic(text: &str, export: &UnusedExport) -> Value {
+    let name = format!("{:?}", export.exported_name.to_string());
+    let range = json!({
+        "start": position(text, export.location),
+        "end": position(text, export.end_location),
+    });
+    match export.used_in_module {
+        false => json!({
+            "range": range,
+            "severity": SEVERITY_HINT,
+            "source": env!("CARGO_PKG_NAME"),
+            "message": format!("{} is never used", name),
+            "tags": [TAG_UNNECESSARY],
+        }),
+        // The declaration is live, so it isn't greyed out.
+        true => json!({
+            "range": range,
+            "severity": SEVERITY_HINT,
+            "source": env!("CARGO_PKG_NAME"),
+            "message": format!(
+                "{} is only used in this file, so it doesn't need to be exported",
+                name
+            ),
+        }),
+    }
+}
+
+// Tests would break without the export, so there's no code action for it.
+fn test_only_diagnostic(text: &str, export: &UnusedExport) -> Value {
+    json!({
+        "range": {
+            "start": position(text, export.location),
+            "end": position(text, export.end_location),
+        },
+        "severity": SEVERITY_HINT,
+        "source": env!("CARGO_PKG_NAME"),
+        "message": format!("{:?} is only used by tests", export.exported_name.to_string()),
+    })
+}
+
+// An action that removes the exports in `fix` from `text`, or `None` if that changes nothing.
+fn code_action(
+    uri: &Value,
+    text: &str,
+    fix: &ModuleResults,
+    title: String,
+    diagnostics: Vec<Value>,
+) -> Option<Value> {
+    let fixed = fix_source(text, fix).ok()?;
+    if fixed == text {
+        return None;
+    }
+    let uri = uri.as_str()?;
+    Some(json!({
+        "title": title,
+        "kind": "quickfix",
+        "diagnostics": diagnostics,
+        "edit": { "changes": { uri: [text_edit(text, &fixed)] } },
+    }))
+}
+
+// A single edit that turns `old` into `new`, replacing what's between their common prefix and
+// suffix.
+fn text_edit(old: &str, new: &str) -> Value {
+    let mut prefix = old
+        .bytes()
+        .zip(new.bytes())
+        .take_while(|(a, b)| a == b)
+        .count();
+    while !old.is_char_boundary(prefix) {
+        prefix -= 1;
+    }
+    let mut suffix = old[prefix..]
+        .bytes()
+        .rev()
+        .zip(new[prefix..].bytes().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    while !old.is_char_boundary(old.len() - suffix) {
+        suffix -= 1;
+    }
+    json!({
+        "range": {
+            "start": offset_position(old, prefix),
+            "end": offset_position(old, old.len() - suffix),
+        },
+        "newText": &new[prefix..new.len() - suffix],
+    })
+}
+
+// LSP positions are 0-based and count UTF-16 code units, while `Location`s are 1-based and
+// count characters.
+fn position(text: &str, location: Location) -> Value {
+    let line = location.line.saturating_sub(1);
+    let character: usize = text
+        .split('\n')
+        .nth(line)
+        .map(|line| {
+            line.chars()
+                .take(location.column.saturating_sub(1))
+                .map(char::len_utf16)
+                .sum()
+        })
+        .unwrap_or_default();
+    json!({ "line": line, "character": character })
+}
+
+fn offset_position(text: &str, offset: usize) -> Value {
+    let before = &text[..offset];
+    let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+    let character: usize = before[line_start..].chars().map(char::len_utf16).sum();
+    json!({ "line": before.matches('\n').count(), "character": character })
+}
+
+fn position_key(position: &Value) -> (u64, u64) {
+    (
+        position["line"].as_u64().unwrap_or_default(),
+        position["character"].as_u64().unwrap_or_default(),
+    )
+}
+
+// Paths are canonical, like those the analysis reports, so the documents can be matched up.
+fn uri_to_path(uri: &str) -> Option<PathBuf> {
+    let path = Url::parse(uri).ok()?.to_file_path().ok()?;
+    Some(canonical_path(path))
+}
+
+fn canonical_path(path: impl AsRef<Path>) -> PathBuf {
+    let path = path.as_ref();
+    fs::canonicalize(path).unwrap_or_else(|_| path.into())
+}
+
+fn path_to_uri(path: &Path) -> Option<String> {
+    Url::from_file_path(path).ok().map(String::from)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Analyzer;
+    use parcel_resolver::{Cache, CacheCow, OsFileSystem, Resolver};
+
+    // Runs a session of `messages`, without a debounce so only code actions analyze, and returns
+    // the messages sent to the client.
+    fn session<F>(messages: &[Value], analyze: F) -> Vec<Value>
+    where
+        F: FnMut(
+            &Path,
+            &HashMap<PathBuf, String>,
+            &BTreeSet<PathBuf>,
+        ) -> Result<AnalysisResults, String>,
+    {
+        let mut input = Vec::new();
+        for message in messages {
+            let body = message.to_string();
+            input.extend(format!("Content-Length: {}\r\n\r\n{}", body.len(), body).bytes());
+        }
+        let mut output = Vec::new();
+        let debounce = Duration::from_secs(3600);
+        serve(io::Cursor::new(input), &mut output, debounce, analyze).unwrap();
+
+        let mut output = io::Cursor::new(output);
+        let mut messages = Vec::new();
+        while let Some(message) = read_message(&mut output).unwrap() {
+            messages.push(message);
+        }
+        messages
+    }
+
+    // Analyzes `file` in testdata, with the contents of the open documents.
+    fn analyze_file(file: &Path, documents: &HashMap<PathBuf, String>) -> AnalysisResults {
+        let resolver = Resolver::parcel(
+            PathBuf::from("testdata").into(),
+            CacheCow::Owned(Cache::new(OsFileSystem)),
+        );
+        let mut analyzer = Analyzer::new(Default::default());
+        for (file, source) in documents {
+            analyzer.add_overlay(file, source.clone());
+        }
+        analyzer.add_file(&resolver, file);
+        analyzer.finalize()
+    }
+
+    fn methods(messages: &[Value]) -> Vec<&Value> {
+        messages.iter().map(|message| &message["method"]).collect()
+    }
+
+    #[test]
+    fn code_actions() {
+        let file = canonical_path("testdata/import_unresolved.ts");
+        let uri = path_to_uri(&file).unwrap();
+        let root = path_to_uri(&canonical_path("testdata")).unwrap();
+        let elsewhere = std::env::temp_dir().join("ts-deadcode-elsewhere/new.ts");
+        let elsewhere = path_to_uri(&elsewhere).unwrap();
+        let messages = [
+            json!({ "id": 1, "method": "initialize", "params": { "rootUri": root } }),
+            json!({ "method": "initialized", "params": {} }),
+            json!({ "method": "textDocument/didOpen", "params": { "textDocument": {
+                "uri": uri,
+                "text": "export const value = 1;\n",
+            } } }),
+            // Unsaved changes are analyzed instead of the file on disk. They're analyzed together
+            // with the changes before them, since the code action comes before the debounce.
+            json!({ "method": "textDocument/didChange", "params": {
+                "textDocument": { "uri": uri },
+                "contentChanges": [{ "text": "export const value = 1;\nexport const unsaved = value;\n" }],
+            } }),
+            // Not analyzed, and not a change that needs publishing.
+            json!({ "method": "textDocument/didOpen", "params": { "textDocument": {
+                "uri": elsewhere,
+                "text": "export const elsewhere = 1;\n",
+            } } }),
+            json!({ "id": 2, "method": "textDocument/codeAction", "params": {
+                "textDocument": { "uri": uri },
+                "range": {
+                    "start": { "line": 1, "character": 15 },
+                    "end": { "line": 1, "character": 15 },
+                },
+            } }),
+            json!({ "id": 3, "method": "shutdown" }),
+            json!({ "method": "exit" }),
+        ];
+
+        let mut analyses = Vec::new();
+        let messages = session(&messages, |root, documents, changed| {
+            assert!(documents.keys().all(|file| file.starts_with(root)));
+            analyses.push(changed.clone());
+            Ok(analyze_file(&file, documents))
+        });
+        assert_eq!(analyses, [BTreeSet::from([file.clone()])]);
+        assert_eq!(
+            methods(&messages),
+            [
+                &Value::Null,
+                &"textDocument/publishDiagnostics".into(),
+                &Value::Null,
+                &Value::Null,
+            ]
+        );
+
+        let diagnostics = &messages[1]["params"]["diagnostics"];
+        assert_eq!(
+            diagnostics[0],
+            json!({
+                "range": {
+                    "start": { "line": 0, "character": 13 },
+                    "end": { "line": 0, "character": 18 },
+                },
+                "severity": 4,
+                "source": "ts-deadcode",
+                "message": "\"value\" is only used in this file, so it doesn't need to be exported",
+            })
+        );
+        assert_eq!(diagnostics[1]["tags"], json!([1]));
+
+        let actions = messages[2]["result"].as_array().unwrap();
+        assert_eq!(actions[0]["title"], "Remove unused export \"unsaved\"");
+        assert_eq!(
+            actions[0]["edit"]["changes"][&uri],
+            json!([{
+                "range": {
+                    "start": { "line": 1, "character": 0 },
+                    "end": { "line": 2, "character": 0 },
+                },
+                "newText": "",
+            }])
+        );
+        assert_eq!(
+            actions[1]["title"],
+            "Remove all unused exports in this file"
+        );
+    }
+
+    #[test]
+    fn cleared_diagnostics() {
+        let file = canonical_path("testdata/import_unresolved.ts");
+        let uri = path_to_uri(&file).unwrap();
+        let root = path_to_uri(&canonical_path("testdata")).unwrap();
+        let request_actions = |id| {
+            json!({ "id": id, "method": "textDocument/codeAction", "params": {
+                "textDocument": { "uri": uri },
+                "range": {
+                    "start": { "line": 0, "character": 0 },
+                    "end": { "line": 0, "character": 0 },
+                },
+            } })
+        };
+        let messages = [
+            json!({ "id": 1, "method": "initialize", "params": { "rootUri": root } }),
+            json!({ "method": "textDocument/didOpen", "params": { "textDocument": {
+                "uri": uri,
+                "text": "export const value = 1;\n",
+            } } }),
+            request_actions(2),
+            // Nothing changed, so this isn't analyzed.
+            request_actions(3),
+            json!({ "method": "textDocument/didClose", "params": { "textDocument": { "uri": uri } } }),
+            request_actions(4),
+            json!({ "method": "textDocument/didSave", "params": { "textDocument": { "uri": uri } } }),
+            request_actions(5),
+            json!({ "method": "exit" }),
+        ];
+
+        let mut analyses = 0;
+        let messages = session(&messages, |_, documents, _| {
+            analyses += 1;
+            match analyses {
+                1 => Ok(analyze_file(&file, documents)),
+                2 => Ok(AnalysisResults::default()),
+                _ => Err("analysis failed".into()),
+            }
+        });
+        assert_eq!(analyses, 3);
+        assert_eq!(
+            methods(&messages),
+            [
+                &Value::Null,
+                &"textDocument/publishDiagnostics".into(),
+                &Value::Null,
+                &Value::Null,
+                // The file has no diagnostics anymore.
+                &"textDocument/publishDiagnostics".into(),
+                &Value::Null,
+                // The results of a failed analysis are kept.
+                &"window/showMessage".into(),
+                &Value::Null,
+            ]
+        );
+        assert_eq!(
+            messages[1]["params"]["diagnostics"]
+                .as_array()
+                .unwrap()
+                .len(),
+            1
+        );
+        assert_eq!(
+            messages[4]["params"],
+            json!({ "uri": uri, "diagnostics": [] })
+        );
+        assert_eq!(messages[6]["params"]["message"], "analysis failed");
+    }
+
+    #[test]
+    fn message_framing() {
+        let body = r#"{"method":"exit"}"#;
+        let input = format!(
+            "content-length: {}\r\nContent-Type: application/vscode-jsonrpc\r\n\r\n{}",
+            body.len(),
+            body
+        );
+        let mut input = io::Cursor::new(input);
+        assert_eq!(
+            read_message(&mut input).unwrap(),
+            Some(json!({ "method": "exit" }))
+        );
+        assert_eq!(read_message(&mut input).unwrap(), None);
+
+        let mut input = io::Cursor::new("Content-Type: application/json\r\n\r\n{}");
+        let err = read_message(&mut input).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn positions() {
+        // "😀" is two UTF-16 code units and four bytes.
+        let text = "\nconst 😀 = 1;\n";
+        let expected = json!({ "line": 1, "character": 9 });
+        assert_eq!(position(text, Location { line: 2, column: 9 }), expected);
+        assert_eq!(offset_position(text, text.find('=').unwrap()), expected);
+        // Past the end of the text
+        assert_eq!(
+            position(text, Location { line: 5, column: 1 }),
+            json!({ "line": 4, "character": 0 })
+        );
+    }
+
+    #[test]
+    fn text_edits() {
+        assert_eq!(
+            text_edit("export const a = 1;\n", "const a = 1;\n"),
+            json!({
+                "range": {
+                    "start": { "line": 0, "character": 0 },
+                    "end": { "line": 0, "character": 7 },
+                },
+                "newText": "",
+            })
+        );
+        // "é" shares its first byte with "è" and its last with "ĩ", but characters aren't split.
+        let edit = |old, new| {
+            let edit = text_edit(old, new);
+            let range = &edit["range"];
+            (
+                range["start"]["character"].clone(),
+                range["end"]["character"].clone(),
+                edit["newText"].clone(),
+            )
+        };
+        assert_eq!(edit("aé", "aè"), (json!(1), json!(2), json!("è")));
+        assert_eq!(edit("ĩb", "éb"), (json!(0), json!(1), json!("é")));
+    }
+}
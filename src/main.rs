@@ -1,151 +1,1034 @@
-use clap::Parser;
-use parcel_resolver::{OsFileSystem, ResolveOptions, Resolver};
-use std::collections::HashMap;
+use clap::{Parser, Subcommand, ValueEnum};
+use parcel_resolver::{Cache, CacheCow, OsFileSystem, ResolveOptions, Resolver, TsConfig};
+use similar::TextDiff;
+use std::collections::{BTreeSet, HashMap};
 use std::env::set_current_dir;
-use std::fs::{self, read_to_string, DirEntry};
-use std::io;
+use std::fs;
+use std::io::{self, BufReader};
 use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::thread::sleep;
+use std::time::Duration;
 
-use ts_deadcode::{Analyzer, ModuleResults};
+use ts_deadcode::baseline::Baseline;
+use ts_deadcode::cache::FactsCache;
+use ts_deadcode::changes::Changes;
+use ts_deadcode::config::{Configs, RuleSeverity};
+use ts_deadcode::entry_points::package_entry_points;
+use ts_deadcode::files::{matches_any, FileFilter};
+use ts_deadcode::fix::fix_source;
+use ts_deadcode::lsp;
+use ts_deadcode::report::{FindingKind, Report};
+use ts_deadcode::sarif::to_sarif;
+use ts_deadcode::watch::Snapshot;
+use ts_deadcode::workspace::workspace_packages;
+use ts_deadcode::{AnalysisResults, Analyzer, Diagnostic, Severity};
 
-fn visit_dirs(dir: &Path, cb: &mut dyn for<'a> FnMut(&'a DirEntry)) -> io::Result<()> {
-    if dir.is_dir() {
-        for entry in fs::read_dir(dir)? {
-            let entry = entry?;
-            let path = entry.path();
-            if path.is_dir() && entry.file_name() != "node_modules" {
-                visit_dirs(&path, cb)?;
-            } else {
-                cb(&entry);
-            }
-        }
-    }
-    Ok(())
+#[derive(Clone, Copy, ValueEnum)]
+enum Format {
+    /// Findings as lines of text, for people.
+    Text,
+    /// A single JSON document with every finding, for other tools.
+    Json,
+    /// SARIF 2.1.0, for code scanning dashboards.
+    Sarif,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Speak the Language Server Protocol over stdio, to show unused exports in editors. The repo
+    /// root is the workspace the editor opens.
+    Lsp,
 }
 
 #[derive(Parser)]
+#[command(subcommand_negates_reqs = true)]
 struct Cli {
-    repo_root: std::path::PathBuf,
+    /// The root of the repo to analyze.
+    #[clap(required = true)]
+    repo_root: Option<PathBuf>,
+
+    #[command(subcommand)]
+    command: Option<Command>,
 
+    /// Don't report unused type exports, e.g. interfaces and type aliases.
     #[clap(long, action)]
     ignore_unused_type_exports: bool,
 
+    /// Don't report unused exports that their own file still uses, where only the `export`
+    /// keyword is unnecessary.
     #[clap(long, action)]
     allow_unused_export_if_used_in_self_module: bool,
 
-    #[clap(short = 'i', long)]
-    ignore: Vec<String>,
+    /// Only analyze files matching one of these globs, relative to the repo root. A glob without
+    /// `/` matches a file or directory name anywhere.
+    #[clap(long, value_name = "GLOB")]
+    include: Vec<String>,
 
+    /// Don't analyze files matching these globs, like `--include`. Files ignored by `.gitignore`
+    /// or `.ignore` files are never analyzed.
+    #[clap(short = 'i', long, value_name = "GLOB", alias = "ignore")]
+    exclude: Vec<String>,
+
+    /// Only analyze the files each package's tsconfig.json includes with its `files`, `include`
+    /// and `exclude`, following `extends`. Packages without a tsconfig.json are analyzed whole.
+    #[clap(long, action)]
+    tsconfig_files: bool,
+
+    /// A condition to match in the `exports` and `imports` of package.json files, besides the
+    /// standard ones. Can be repeated.
     #[clap(long = "custom-condition")]
     custom_conditions: Vec<String>,
 
+    /// Files that are tests, as globs like `--include`. Exports that only tests use are reported
+    /// separately. Replaces the globs of configs and the defaults, `*.test.*`, `*.spec.*` and
+    /// `__tests__`.
+    #[clap(long = "test", value_name = "GLOB")]
+    test_globs: Vec<String>,
+
+    /// Don't analyze test files at all.
     #[clap(long, action)]
     ignore_tests: bool,
+
+    /// Files used from outside the repo, as paths or globs relative to the repo root. Once there
+    /// are entry points, only code reachable from them counts as using anything.
+    #[clap(long = "entry")]
+    entry_points: Vec<String>,
+
+    /// Use the `main`, `bin`, `exports`, ... targets of each package.json as entry points.
+    #[clap(long, action)]
+    package_entry_points: bool,
+
+    /// Treat test files as entry points, so they and the code they use aren't reported.
+    #[clap(long, action)]
+    tests_as_entry_points: bool,
+
+    /// How to print the findings.
+    #[clap(long, value_enum, default_value_t = Format::Text)]
+    format: Format,
+
+    /// Remove unused exports: drop the `export` keyword where the declaration is still used in
+    /// its file, and delete it otherwise.
+    #[clap(long, action)]
+    fix: bool,
+
+    /// With `--fix`, print the changes as a unified diff instead of writing them.
+    #[clap(long, action, requires = "fix")]
+    dry_run: bool,
+
+    /// Record the current findings in this file, to pass to `--baseline` later.
+    #[clap(long)]
+    write_baseline: Option<PathBuf>,
+
+    /// Only report findings that aren't in this baseline, and list its entries that are fixed.
+    #[clap(long)]
+    baseline: Option<PathBuf>,
+
+    /// Exit with code 1 if there are more findings than this.
+    #[clap(long)]
+    max_findings: Option<usize>,
+
+    /// Exit with code 1 if there are more findings of a kind than allowed, e.g.
+    /// `--max unused-file=0`.
+    #[clap(long, value_name = "KIND=MAX", value_parser = parse_limit)]
+    max: Vec<(FindingKind, usize)>,
+
+    /// Number of threads to analyze files on. Defaults to one per CPU.
+    #[clap(short = 'j', long)]
+    jobs: Option<usize>,
+
+    /// Reuse the facts collected from files that haven't changed since the last run, and store
+    /// them in this file for the next one.
+    #[clap(long, value_name = "FILE")]
+    cache: Option<PathBuf>,
+
+    /// Keep running, and analyze the files again whenever something in the repo changes. Prints
+    /// the findings that appeared or went away after each change.
+    #[clap(long, action, conflicts_with_all = ["fix", "write_baseline", "format"])]
+    watch: bool,
+
+    /// Only report findings the changes since this git revision are responsible for: those in
+    /// changed files, and those that appeared, e.g. exports whose last importer was removed. The
+    /// whole repo is still analyzed.
+    #[clap(long, value_name = "REV", conflicts_with = "watch")]
+    since: Option<String>,
+
+    /// Print statistics about the analysis to stderr.
+    #[clap(long, action)]
+    stats: bool,
 }
 
-fn main() {
-    let args = Cli::parse();
+// More findings than `--max-findings` or `--max` allow, or findings whose rule is an error.
+const EXIT_FINDINGS: u8 = 1;
+// The tool failed, or a file couldn't be analyzed. Also used by clap for invalid arguments.
+const EXIT_FAILURE: u8 = 2;
+
+const DEFAULT_TEST_GLOBS: [&str; 3] = ["*.test.*", "*.spec.*", "__tests__"];
+
+// How often `--watch` checks the repo for changes.
+const POLL_INTERVAL: Duration = Duration::from_millis(300);
+// How long the language server waits for more changes before analyzing them, e.g. while the
+// user is typing.
+const LSP_DEBOUNCE: Duration = Duration::from_millis(200);
 
-    set_current_dir(&args.repo_root).expect("should set current dir");
+fn is_entry_point(patterns: &[String], relative_path: &str) -> bool {
+    patterns.iter().any(|pattern| {
+        let pattern = pattern.trim_start_matches("./");
+        pattern == relative_path || glob_match::glob_match(pattern, relative_path)
+    })
+}
 
-    // Find internal packages to build resolver map.
-    let mut resolvers = HashMap::new();
-    visit_dirs(&args.repo_root, &mut |entry: &DirEntry| {
-        if entry.file_name() != "package.json" {
-            return;
+// e.g. `unused-file=0`
+fn parse_limit(value: &str) -> Result<(FindingKind, usize), String> {
+    let (id, max) = value
+        .split_once('=')
+        .ok_or("expected <KIND>=<MAX>, e.g. unused-file=0")?;
+    let kind = FindingKind::from_id(id).ok_or_else(|| {
+        let ids: Vec<_> = FindingKind::ALL.iter().map(|kind| kind.id()).collect();
+        format!(
+            "unknown finding kind {:?}, expected one of {}",
+            id,
+            ids.join(", ")
+        )
+    })?;
+    let max = max
+        .parse()
+        .map_err(|err| format!("invalid maximum {:?}: {}", max, err))?;
+    Ok((kind, max))
+}
+
+fn main() -> ExitCode {
+    let args = Cli::parse();
+    match run(args) {
+        Ok(exit_code) => exit_code,
+        Err(message) => {
+            eprintln!("ERROR: {}", message);
+            ExitCode::from(EXIT_FAILURE)
         }
+    }
+}
+
+fn run(args: Cli) -> Result<ExitCode, String> {
+    // Relative to where we're run from, not the repo root.
+    let write_baseline = match &args.write_baseline {
+        Some(path) => Some(std::path::absolute(path).map_err(|err| err.to_string())?),
+        None => None,
+    };
+    let cache_path = match &args.cache {
+        Some(path) => Some(std::path::absolute(path).map_err(|err| err.to_string())?),
+        None => None,
+    };
+    let baseline = match &args.baseline {
+        Some(path) => Some(
+            Baseline::read(path).map_err(|message| format!("{}: {}", path.display(), message))?,
+        ),
+        None => None,
+    };
+
+    if let Some(jobs) = args.jobs {
+        rayon::ThreadPoolBuilder::new()
+            .num_threads(jobs)
+            .build_global()
+            .map_err(|err| err.to_string())?;
+    }
+
+    if let Some(Command::Lsp) = args.command {
+        // Kept between analyses, so only the changes are analyzed. If analyzing them fails, the
+        // next analysis starts over, reusing the facts of the files that didn't change.
+        let mut project: Option<Project> = None;
+        let mut facts_cache = FactsCache::default();
+        let input = BufReader::new(io::stdin());
+        lsp::serve(
+            input,
+            io::stdout(),
+            LSP_DEBOUNCE,
+            |repo_root, documents, changed| {
+                let kept = match project.take() {
+                    Some(mut kept) => {
+                        kept.set_overlays(documents);
+                        kept.update(changed, Some(&mut facts_cache))?;
+                        kept
+                    }
+                    None => Project::new(&args, repo_root, documents, Some(&mut facts_cache))?,
+                };
+                Ok(project.insert(kept).results())
+            },
+        )?;
+        return Ok(ExitCode::SUCCESS);
+    }
+
+    let repo_root_arg = args
+        .repo_root
+        .as_ref()
+        .expect("required without a subcommand");
+    let repo_root = fs::canonicalize(repo_root_arg)
+        .map_err(|err| format!("{}: {}", repo_root_arg.display(), err))?;
+    set_current_dir(&repo_root).map_err(|err| err.to_string())?;
 
-        let project = PathBuf::from(entry.path().parent().unwrap());
-        let resolver = Resolver::parcel(
-            project.clone().into(),
-            parcel_resolver::CacheCow::Owned(parcel_resolver::Cache::new(OsFileSystem)),
+    let mut facts_cache = cache_path.as_deref().map(FactsCache::read);
+    if args.watch {
+        let facts_cache = facts_cache.unwrap_or_default();
+        return watch(
+            &args,
+            &repo_root,
+            baseline.as_ref(),
+            facts_cache,
+            cache_path,
         );
-        resolvers.insert(project, resolver);
-    })
-    .expect("Failed to build resolver map");
+    }
+    // The repo as it was at the revision, analyzed first so that the cache ends up with the
+    // facts of the current files.
+    let since = match &args.since {
+        Some(rev) => {
+            let changes = Changes::since(&repo_root, rev)?;
+            let old_project = Project::new(
+                &args,
+                &repo_root,
+                &changes.old_contents,
+                facts_cache.as_mut(),
+            )?;
+            Some((changes, old_project.results()))
+        }
+        None => None,
+    };
+    let project = Project::new(&args, &repo_root, &HashMap::new(), facts_cache.as_mut())?;
+    let mut results = project.results();
+    if let Some((changes, old_results)) = &since {
+        changes.retain_attributable(&mut results, old_results, &repo_root);
+    }
+    if let (Some(facts_cache), Some(path)) = (&mut facts_cache, &cache_path) {
+        facts_cache
+            .write(path)
+            .map_err(|message| format!("{}: {}", path.display(), message))?;
+    }
+    if let Some(path) = write_baseline {
+        let baseline = Baseline::new(&results, &repo_root);
+        baseline
+            .write(&path)
+            .map_err(|message| format!("{}: {}", path.display(), message))?;
+        println!("WROTE {} ENTRIES TO {:?}", baseline.entries.len(), path);
+        return Ok(ExitCode::SUCCESS);
+    }
+    let fixed_baseline_entries = match baseline {
+        Some(baseline) => baseline.apply(&mut results, &repo_root),
+        None => Vec::new(),
+    };
 
-    let mut analyzer = Analyzer::new(ResolveOptions {
-        conditions: Default::default(),
-        custom_conditions: args.custom_conditions,
-    });
+    if args.fix {
+        return fix(&results, &repo_root, args.dry_run);
+    }
 
-    // Specify the directory containing the files to be parsed
-    let dir_path = Path::new(&args.repo_root);
+    let mut report = Report::new(&results);
+    report.fixed_baseline_entries = fixed_baseline_entries;
 
-    visit_dirs(dir_path, &mut |entry: &DirEntry| {
-        let file_path = entry.path();
+    match args.format {
+        Format::Text => print_text(&report),
+        Format::Json => println!("{}", report.to_json()?),
+        Format::Sarif => {
+            let sarif = to_sarif(&report, &repo_root);
+            let sarif = serde_json::to_string_pretty(&sarif).map_err(|err| err.to_string())?;
+            println!("{}", sarif);
+        }
+    }
 
-        for item in &args.ignore {
-            if file_path.iter().any(|c| item == c.to_str().unwrap()) {
-                return;
+    let (exit_code, errors) = exit_code(&args, &report, &project.configs);
+    for error in errors {
+        eprintln!("ERROR: {}", error);
+    }
+    Ok(ExitCode::from(exit_code))
+}
+
+// The exit code for `report`, with the reasons it isn't success.
+fn exit_code(args: &Cli, report: &Report, configs: &Configs) -> (u8, Vec<String>) {
+    // The results are incomplete, so they can't be trusted either way.
+    if report
+        .diagnostics
+        .iter()
+        .any(|diagnostic| diagnostic.severity == Severity::Error)
+    {
+        return (EXIT_FAILURE, Vec::new());
+    }
+
+    let mut errors = Vec::new();
+    if let Some(max) = args.max_findings {
+        let total = total_findings(report);
+        if total > max {
+            errors.push(format!("{} findings exceed --max-findings {}", total, max));
+        }
+    }
+    for (kind, max) in &args.max {
+        let count = report.count(*kind);
+        if count > *max {
+            errors.push(format!(
+                "{} {} findings exceed --max {}={}",
+                count,
+                kind.id(),
+                kind.id(),
+                max
+            ));
+        }
+    }
+    let severe = report
+        .findings()
+        .into_iter()
+        .filter(|(file, kind)| configs.severity(file, *kind) == RuleSeverity::Error)
+        .count();
+    if severe > 0 {
+        errors.push(format!(
+            "{} findings have a rule with severity error",
+            severe
+        ));
+    }
+    match errors.is_empty() {
+        true => (0, errors),
+        false => (EXIT_FINDINGS, errors),
+    }
+}
+
+// The packages and files of a repo, analyzed according to `args` and the configs of the repo.
+// `--watch` and the language server keep it, so that after a change only the files the change
+// affects are analyzed again.
+struct Project<'a> {
+    args: &'a Cli,
+    repo_root: PathBuf,
+    filter: FileFilter,
+    configs: Configs,
+    packages: Vec<PathBuf>,
+    // The entry points of the packages, with `--package-entry-points`
+    entry_points: Vec<PathBuf>,
+    // Packages share one cache, since they mostly look up the same files, package.jsons and
+    // tsconfigs, e.g. those of the workspace root and its node_modules.
+    cache: Cache<OsFileSystem>,
+    analyzer: Analyzer,
+    // The files `FileFilter::walk` found
+    files: BTreeSet<PathBuf>,
+    // file -> contents that replace the file on disk, e.g. unsaved changes in an editor, or stand
+    // in for a file that isn't on disk
+    overlays: HashMap<PathBuf, String>,
+}
+
+impl<'a> Project<'a> {
+    // Finds the packages and files in `repo_root` and analyzes them, reusing the facts in
+    // `facts_cache` where possible.
+    fn new(
+        args: &'a Cli,
+        repo_root: &Path,
+        overlays: &HashMap<PathBuf, String>,
+        facts_cache: Option<&mut FactsCache>,
+    ) -> Result<Self, String> {
+        let filter = FileFilter {
+            include: args.include.clone(),
+            exclude: args.exclude.clone(),
+        };
+        let (walked, mut diagnostics) = filter.walk(repo_root);
+        let package_dirs: Vec<PathBuf> = walked
+            .iter()
+            .filter(|file| file.file_name().unwrap_or_default() == "package.json")
+            .map(|package_json| package_json.parent().unwrap().into())
+            .collect();
+        // In a workspace, other package.jsons, e.g. those of test fixtures, belong to the package
+        // they're in.
+        let packages = match workspace_packages(repo_root, &package_dirs) {
+            Ok(Some(packages)) => packages,
+            Ok(None) => package_dirs,
+            Err(diagnostic) => {
+                diagnostics.push(diagnostic);
+                package_dirs
+            }
+        };
+        let mut config_dirs = packages.clone();
+        config_dirs.push(repo_root.into());
+        let configs = Configs::read(&config_dirs)?;
+        let mut entry_points = Vec::new();
+        if args.package_entry_points {
+            for project in &packages {
+                match package_entry_points(project) {
+                    Ok(package_entry_points) => entry_points.extend(package_entry_points),
+                    Err(message) => diagnostics.push(Diagnostic {
+                        severity: Severity::Warning,
+                        file: project.join("package.json"),
+                        message,
+                    }),
+                }
             }
         }
 
-        let filename = file_path.to_str().unwrap();
-        if filename.ends_with(".d.ts") {
-            return;
+        let mut custom_conditions = args.custom_conditions.clone();
+        for condition in configs.custom_conditions() {
+            if !custom_conditions.contains(condition) {
+                custom_conditions.push(condition.clone());
+            }
+        }
+        let mut analyzer = Analyzer::new(ResolveOptions {
+            conditions: Default::default(),
+            custom_conditions,
+        });
+        for (file, source) in overlays {
+            analyzer.add_overlay(file, source.clone());
         }
 
-        if args.ignore_tests && filename.ends_with(".test.tsx") {
-            return;
+        let mut project = Self {
+            args,
+            repo_root: repo_root.into(),
+            filter,
+            configs,
+            packages,
+            entry_points,
+            cache: Cache::new(OsFileSystem),
+            analyzer,
+            files: walked.iter().cloned().collect(),
+            overlays: overlays.clone(),
+        };
+        let resolvers = package_resolvers(&project.packages, &project.cache);
+        package_tsconfigs(args, &resolvers, &mut diagnostics);
+
+        // Overlays may stand in for files that aren't on disk, e.g. ones deleted since `--since`.
+        let mut files = walked;
+        let mut missing: Vec<PathBuf> = overlays
+            .keys()
+            .filter(|file| !file.exists())
+            .cloned()
+            .collect();
+        missing.sort();
+        files.extend(missing);
+        project.analyze(files, facts_cache);
+        // After the files, since analyzing a file drops the diagnostics reported for it
+        for diagnostic in diagnostics {
+            project.analyzer.add_diagnostic(diagnostic);
+        }
+        Ok(project)
+    }
+
+    // Analyzes `overlays` instead of the files on disk from now on. The files whose overlay
+    // changed have to be passed to `update`.
+    fn set_overlays(&mut self, overlays: &HashMap<PathBuf, String>) {
+        for file in self.overlays.keys() {
+            if !overlays.contains_key(file) {
+                self.analyzer.remove_overlay(file);
+            }
         }
+        for (file, source) in overlays {
+            self.analyzer.add_overlay(file, source.clone());
+        }
+        self.overlays = overlays.clone();
+    }
 
-        let ext = file_path.extension().unwrap_or_default();
-        if ext == "ts"
-            || ext == "tsx"
-            || ext == "js"
-            || ext == "jsx"
-            || ext == "mjs"
-            || ext == "cjs"
+    // Analyzes the project again after the `changed` files were created, changed or deleted.
+    // Only they and the files whose imports may resolve differently now are analyzed again,
+    // unless the change may affect the whole project, e.g. that of a package.json.
+    fn update(
+        &mut self,
+        changed: &BTreeSet<PathBuf>,
+        facts_cache: Option<&mut FactsCache>,
+    ) -> Result<(), String> {
+        if changed.iter().any(|file| is_project_file(file)) {
+            *self = Self::new(self.args, &self.repo_root, &self.overlays, facts_cache)?;
+            return Ok(());
+        }
+        for file in changed {
+            self.cache.invalidate(file);
+        }
+        // New files are only analyzed if `FileFilter::walk` finds them, e.g. if they aren't
+        // ignored. Its warnings were reported by `new`.
+        if changed
+            .iter()
+            .any(|file| !self.files.contains(file) && file.is_file())
         {
-            // Find the resolver for the nearest enclosing project
-            let mut package_path = file_path.clone();
-            while let Some(package_path_parent) = package_path.parent() {
-                if let Some(resolver) = resolvers.get(package_path_parent) {
-                    analyzer.add_file(resolver, &file_path);
-                    return;
+            self.files = self.filter.walk(&self.repo_root).0.into_iter().collect();
+        }
+
+        let dependents = self.analyzer.dependents(changed);
+        let mut files = BTreeSet::new();
+        for file in changed.iter().chain(&dependents) {
+            if !file.is_file() {
+                self.files.remove(file);
+            }
+            // Overlays only stand in for files that aren't on disk, like in `new`.
+            let analyzed = match file.exists() {
+                true => self.files.contains(file),
+                false => self.overlays.contains_key(file),
+            };
+            match analyzed {
+                true => {
+                    files.insert(file.clone());
                 }
-                package_path = package_path_parent.into();
+                // Deleted, or never analyzed
+                false => self.analyzer.remove_file(file),
             }
-            println!("no resolver for {:?}", file_path);
         }
-    })
-    .expect("should not fail");
+        self.analyze(files.into_iter().collect(), facts_cache);
+        Ok(())
+    }
 
-    let mut count = 0;
+    // Finds out how to analyze each of `files`, e.g. with the resolver of which package, and
+    // whether it's analyzed at all, and collects their facts. Replaces whatever the analyzer
+    // knew about them.
+    fn analyze(&mut self, files: Vec<PathBuf>, facts_cache: Option<&mut FactsCache>) {
+        let Self {
+            args,
+            repo_root,
+            filter,
+            configs,
+            packages,
+            entry_points,
+            cache,
+            analyzer,
+            ..
+        } = self;
+        let resolvers = package_resolvers(packages, cache);
+        // `new` reported the invalid ones.
+        let tsconfigs = package_tsconfigs(args, &resolvers, &mut Vec::new());
 
-    let results = analyzer.finalize();
-    let mut files: Vec<(&PathBuf, &ModuleResults)> = results.iter().collect();
-    files.sort_by_key(|(k, _)| *k);
-    for (file, module_results) in files {
-        let mut export_providers = vec![&module_results.unused_exports];
-        if !args.ignore_unused_type_exports {
-            export_providers.push(&module_results.unused_type_exports);
+        let mut collected = Vec::new();
+        let default_test_globs = DEFAULT_TEST_GLOBS.map(String::from);
+
+        let mut add_file = |file_path: PathBuf| {
+            analyzer.remove_file(&file_path);
+            let Ok(relative_path) = file_path.strip_prefix(&repo_root) else {
+                return;
+            };
+            if !filter.is_included(relative_path) || configs.is_ignored(&file_path) {
+                return;
+            }
+
+            let filename = file_path.to_str().unwrap();
+            if filename.ends_with(".d.ts") {
+                return;
+            }
+
+            let is_test = match args.test_globs.is_empty() {
+                false => matches_any(&args.test_globs, relative_path),
+                true => configs
+                    .is_test_file(&file_path)
+                    .unwrap_or_else(|| matches_any(&default_test_globs, relative_path)),
+            };
+            if args.ignore_tests && is_test {
+                return;
+            }
+
+            let ext = file_path.extension().unwrap_or_default();
+            if ext == "ts"
+                || ext == "tsx"
+                || ext == "js"
+                || ext == "jsx"
+                || ext == "mjs"
+                || ext == "cjs"
+            {
+                // Find the resolver for the nearest enclosing project
+                let mut package_path = file_path.clone();
+                while let Some(package_path_parent) = package_path.parent() {
+                    if let Some(resolver) = resolvers.get(package_path_parent) {
+                        if let Some(tsconfig) = tsconfigs.get(package_path_parent) {
+                            if !tsconfig.includes_file(&file_path) {
+                                return;
+                            }
+                        }
+                        if is_entry_point(&args.entry_points, relative_path.to_str().unwrap())
+                            || configs.is_entry_point(&file_path)
+                            || (args.tests_as_entry_points && is_test)
+                        {
+                            analyzer.add_entry_point(&file_path);
+                        }
+                        if is_test {
+                            analyzer.add_test_file(&file_path);
+                        }
+                        collected.push((resolver, file_path));
+                        return;
+                    }
+                    package_path = package_path_parent.into();
+                }
+                analyzer.add_diagnostic(Diagnostic {
+                    severity: Severity::Warning,
+                    file: file_path,
+                    message: "no package.json to resolve imports from".into(),
+                });
+            }
+        };
+        for file_path in files {
+            add_file(file_path);
+        }
+        match facts_cache {
+            Some(facts_cache) => {
+                analyzer.add_files_cached(&collected, facts_cache);
+                if args.stats {
+                    let stats = facts_cache.stats();
+                    eprintln!(
+                        "FACTS CACHE: {} reused, {} analyzed, {} specifiers resolved again",
+                        stats.reused, stats.analyzed, stats.reresolved
+                    );
+                }
+            }
+            None => analyzer.add_files(&collected),
+        }
+        if args.stats {
+            let stats = cache.stats();
+            eprintln!(
+                "RESOLVER CACHE: {} hits, {} misses ({:.1}% hit rate)",
+                stats.hits,
+                stats.misses,
+                stats.hit_rate() * 100.0
+            );
+        }
+
+        // Also if they were analyzed again
+        for entry_point in entry_points.iter() {
+            analyzer.add_entry_point(entry_point);
+        }
+    }
+
+    // The findings, filtered according to `args` and the configs of the repo.
+    fn results(&self) -> AnalysisResults {
+        let mut results = self.analyzer.finalize();
+        self.configs.apply(&mut results);
+        // Deleting the file takes care of these.
+        let unused_files = &results.unused_files;
+        results
+            .modules
+            .retain(|file, _| !unused_files.contains(file));
+        for module_results in results.modules.values_mut() {
+            if self.args.ignore_unused_type_exports {
+                module_results.unused_type_exports.clear();
+            }
+            if self.args.allow_unused_export_if_used_in_self_module {
+                module_results
+                    .unused_exports
+                    .retain(|export| !export.used_in_module);
+                module_results
+                    .unused_type_exports
+                    .retain(|export| !export.used_in_module);
+            }
         }
+        results
+    }
+}
 
-        for unused_exports in export_providers {
-            let c = unused_exports.len();
-            if c == 0 {
-                continue;
+// A resolver for each package.
+fn package_resolvers<'a>(
+    packages: &'a [PathBuf],
+    cache: &'a Cache<OsFileSystem>,
+) -> HashMap<&'a Path, Resolver<'a, OsFileSystem>> {
+    packages
+        .iter()
+        .map(|package| {
+            let resolver = Resolver::parcel(package.as_path().into(), CacheCow::Borrowed(cache));
+            (package.as_path(), resolver)
+        })
+        .collect()
+}
+
+// The tsconfig.json of each package that has a valid one, with `--tsconfig-files`.
+fn package_tsconfigs<'a>(
+    args: &Cli,
+    resolvers: &'a HashMap<&'a Path, Resolver<'a, OsFileSystem>>,
+    diagnostics: &mut Vec<Diagnostic>,
+) -> HashMap<&'a Path, &'a TsConfig<'a>> {
+    let mut tsconfigs = HashMap::new();
+    if !args.tsconfig_files {
+        return tsconfigs;
+    }
+    for (project, resolver) in resolvers {
+        let path = project.join("tsconfig.json");
+        if !path.is_file() {
+            continue;
+        }
+        match resolver.read_tsconfig(path.clone(), &Default::default()) {
+            Ok(tsconfig) => {
+                tsconfigs.insert(*project, tsconfig);
             }
+            Err(err) => diagnostics.push(Diagnostic {
+                severity: Severity::Warning,
+                file: path,
+                message: format!("invalid tsconfig: {:?}", err),
+            }),
+        }
+    }
+    tsconfigs
+}
+
+// Whether a change to `file` may change which files are analyzed and how, or how imports resolve
+// in ways that `Analyzer::dependents` doesn't track, so the project has to be set up again:
+// package.jsons, tsconfigs, which may extend any JSON file, configs, and workspace and ignore
+// files.
+fn is_project_file(file: &Path) -> bool {
+    let name = file.file_name().unwrap_or_default();
+    file.extension().is_some_and(|ext| ext == "json")
+        || name == "pnpm-workspace.yaml"
+        || name == ".gitignore"
+        || name == ".ignore"
+}
+
+// Analyzes the repo whenever it changes, until interrupted. The project is kept, so only the
+// files a change affects are analyzed again. Failures are reported, and the changes are analyzed
+// again with the next ones.
+fn watch(
+    args: &Cli,
+    repo_root: &Path,
+    baseline: Option<&Baseline>,
+    mut facts_cache: FactsCache,
+    cache_path: Option<PathBuf>,
+) -> Result<ExitCode, String> {
+    let filter = FileFilter {
+        include: args.include.clone(),
+        exclude: args.exclude.clone(),
+    };
+    let snapshot = || Snapshot::take(repo_root, &filter);
+    let mut last_snapshot = snapshot();
+    let mut project: Option<Project> = None;
+    // The files changed since the last successful analysis
+    let mut changes = BTreeSet::new();
+    let mut previous: Option<Vec<String>> = None;
+    loop {
+        let analyzed = match &mut project {
+            Some(project) => project.update(&changes, Some(&mut facts_cache)),
+            None => Project::new(args, repo_root, &HashMap::new(), Some(&mut facts_cache))
+                .map(|new| project = Some(new)),
+        };
+        if let Err(message) = analyzed {
+            eprintln!("ERROR: {}", message);
+        } else if let Some(project) = &project {
+            changes.clear();
+            if let Some(path) = &cache_path {
+                if let Err(message) = facts_cache.write(path) {
+                    eprintln!("ERROR: {}: {}", path.display(), message);
+                }
+            }
+            let mut results = project.results();
+            let fixed_baseline_entries = match baseline {
+                Some(baseline) => baseline.apply(&mut results, repo_root),
+                None => Vec::new(),
+            };
+            let mut report = Report::new(&results);
+            report.fixed_baseline_entries = fixed_baseline_entries;
 
-            let contents = read_to_string(file).expect("should read file");
-            for export in unused_exports {
-                let export = export.to_string();
-                let first_usage = contents.find(&export).unwrap();
-                match contents[first_usage + 1..].find(&export) {
-                    None => {
-                        println!("{:?}: {:?}", file, export);
-                        count += 1;
+            let lines = text_lines(&report);
+            match &previous {
+                None => print_text(&report),
+                Some(previous) => {
+                    for line in previous.iter().filter(|line| !lines.contains(line)) {
+                        println!("- {}", line);
                     }
-                    _ => {
-                        if !args.allow_unused_export_if_used_in_self_module {
-                            println!("{:?}: {:?} [USED IN FILE]", file, export);
-                        }
+                    for line in lines.iter().filter(|line| !previous.contains(line)) {
+                        println!("+ {}", line);
                     }
+                    println!("TOTAL RESULTS: {}", total_findings(&report));
                 }
             }
+            previous = Some(lines);
         }
+
+        loop {
+            sleep(POLL_INTERVAL);
+            let next_snapshot = snapshot();
+            let changed: Vec<_> = last_snapshot
+                .changes(&next_snapshot)
+                .into_iter()
+                // Writing the cache isn't a change.
+                .filter(|file| Some(file) != cache_path.as_ref())
+                .collect();
+            last_snapshot = next_snapshot;
+            if !changed.is_empty() {
+                changes.extend(changed);
+                break;
+            }
+        }
+    }
+}
+
+fn total_findings(report: &Report) -> usize {
+    FindingKind::ALL
+        .iter()
+        .map(|kind| report.count(*kind))
+        .sum()
+}
+
+fn print_text(report: &Report) {
+    for line in text_lines(report) {
+        println!("{}", line);
+    }
+    println!("TOTAL RESULTS: {}", total_findings(report));
+}
+
+// The diagnostics and findings of `report`, one per line.
+fn text_lines(report: &Report) -> Vec<String> {
+    let mut lines = Vec::new();
+    for diagnostic in &report.diagnostics {
+        let severity = match diagnostic.severity {
+            Severity::Warning => "WARNING",
+            Severity::Error => "ERROR",
+        };
+        lines.push(format!(
+            "{}: {}: {}",
+            severity,
+            diagnostic.file.display(),
+            diagnostic.message
+        ));
+    }
+
+    let mut unused_exports: Vec<_> = report
+        .unused_exports
+        .iter()
+        .chain(&report.unused_type_exports)
+        .collect();
+    unused_exports.sort_by_key(|unused| unused.file);
+    for unused in unused_exports {
+        let export = unused.export;
+        let location = format!(
+            "{}:{}:{}",
+            unused.file.display(),
+            export.location.line,
+            export.location.column
+        );
+        let mut name = format!("{:?}", export.exported_name.to_string());
+        if export.exported_name != export.original_name {
+            name += &format!(" (declared as {:?})", export.original_name.to_string());
+        }
+
+        if !export.used_in_module {
+            lines.push(format!("{}: {} [DEAD DECLARATION]", location, name));
+        } else {
+            lines.push(format!(
+                "{}: {} [USED IN FILE, EXPORT KEYWORD UNNECESSARY]",
+                location, name
+            ));
+        }
+    }
+
+    for test_only in &report.test_only_exports {
+        lines.push(format!(
+            "{}:{}:{}: {:?} [USED ONLY BY TESTS]",
+            test_only.file.display(),
+            test_only.export.location.line,
+            test_only.export.location.column,
+            test_only.export.exported_name.to_string()
+        ));
+    }
+
+    for file in &report.unused_files {
+        lines.push(format!("{:?} [UNUSED FILE]", file));
+    }
+
+    for missing in &report.missing_exports {
+        lines.push(format!(
+            "{}:{}:{}: {:?} [MISSING EXPORT IN {:?}]",
+            missing.importer.display(),
+            missing.location.line,
+            missing.location.column,
+            missing.symbol.to_string(),
+            missing.source
+        ));
+    }
+
+    for unresolved in &report.unresolved_imports {
+        lines.push(format!(
+            "{}:{}:{}: {:?} [UNRESOLVED IMPORT]",
+            unresolved.importer.display(),
+            unresolved.location.line,
+            unresolved.location.column,
+            unresolved.specifier.to_string()
+        ));
+    }
+
+    for stale in &report.stale_suppressions {
+        lines.push(format!(
+            "{}:{}:{}: {:?} [STALE SUPPRESSION]",
+            stale.file.display(),
+            stale.location.line,
+            stale.location.column,
+            stale.directive
+        ));
+    }
+
+    for entry in &report.fixed_baseline_entries {
+        lines.push(format!(
+            "{}: {:?} [{}] [FIXED, REMOVE FROM BASELINE]",
+            entry.file, entry.symbol, entry.kind
+        ));
+    }
+    lines
+}
+
+fn fix(results: &AnalysisResults, repo_root: &Path, dry_run: bool) -> Result<ExitCode, String> {
+    let mut files: Vec<_> = results.modules.iter().collect();
+    files.sort_by_key(|(file, _)| *file);
+    for (file, module_results) in files {
+        let source =
+            fs::read_to_string(file).map_err(|err| format!("{}: {}", file.display(), err))?;
+        let fixed = fix_source(&source, module_results)
+            .map_err(|message| format!("{}: {}", file.display(), message))?;
+        if fixed == source {
+            continue;
+        }
+
+        if dry_run {
+            let name = file.strip_prefix(repo_root).unwrap_or(file).display();
+            let diff = TextDiff::from_lines(&source, &fixed);
+            print!(
+                "{}",
+                diff.unified_diff()
+                    .header(&format!("a/{}", name), &format!("b/{}", name))
+            );
+        } else {
+            fs::write(file, fixed).map_err(|err| format!("{}: {}", file.display(), err))?;
+            println!("{:?} [FIXED]", file);
+        }
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+    use ts_deadcode::{Location, UnresolvedImport};
+
+    // Two unused files and an unresolved import
+    fn results() -> AnalysisResults {
+        AnalysisResults {
+            unused_files: HashSet::from(["/repo/a.ts".into(), "/repo/b.ts".into()]),
+            unresolved_imports: HashSet::from([UnresolvedImport {
+                importer: "/repo/a.ts".into(),
+                specifier: "./missing".into(),
+                location: Location { line: 1, column: 1 },
+            }]),
+            ..Default::default()
+        }
+    }
+
+    fn exit_code_with(args: &[&str], results: &AnalysisResults) -> (u8, usize) {
+        let args = Cli::parse_from(["ts-deadcode"].iter().chain(args).chain(&["/repo"]));
+        let (exit_code, errors) = exit_code(&args, &Report::new(results), &Configs::default());
+        (exit_code, errors.len())
+    }
+
+    #[test]
+    fn exit_codes() {
+        let results = results();
+        assert_eq!(exit_code_with(&[], &results), (0, 0));
+        assert_eq!(exit_code_with(&["--max-findings", "3"], &results), (0, 0));
+        assert_eq!(
+            exit_code_with(&["--max-findings", "2"], &results),
+            (EXIT_FINDINGS, 1)
+        );
+        assert_eq!(
+            exit_code_with(&["--max", "unused-file=2"], &results),
+            (0, 0)
+        );
+        assert_eq!(
+            exit_code_with(
+                &["--max", "unused-file=1", "--max", "unresolved-import=0"],
+                &results
+            ),
+            (EXIT_FINDINGS, 2)
+        );
+
+        // Incomplete results fail the run whatever the findings.
+        let mut failed = results;
+        failed.diagnostics.push(Diagnostic {
+            severity: Severity::Error,
+            file: "/repo/c.ts".into(),
+            message: "failed to parse".into(),
+        });
+        assert_eq!(exit_code_with(&[], &failed), (EXIT_FAILURE, 0));
+        assert!(parse_limit("unused-thing=0").is_err());
     }
-    println!("TOTAL RESULTS: {}", count);
 }
@@ -0,0 +1,189 @@
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::baseline::BaselineEntry;
+use crate::{
+    AnalysisResults, Diagnostic, MissingExport, StaleSuppression, UnresolvedImport, UnusedExport,
+};
+
+// Bump whenever the shape of the report changes, so consumers can tell.
+pub const REPORT_VERSION: u32 = 5;
+
+// A serializable view of `AnalysisResults` for other tools. Every list is sorted, so the same
+// results always produce the same document.
+#[derive(Serialize)]
+pub struct Report<'a> {
+    pub version: u32,
+    pub unused_exports: Vec<FileExport<'a>>,
+    pub unused_type_exports: Vec<FileExport<'a>>,
+    pub test_only_exports: Vec<FileExport<'a>>,
+    pub unused_files: Vec<&'a Path>,
+    pub missing_exports: Vec<&'a MissingExport>,
+    pub unresolved_imports: Vec<&'a UnresolvedImport>,
+    pub stale_suppressions: Vec<&'a StaleSuppression>,
+    pub diagnostics: Vec<&'a Diagnostic>,
+    // Entries of the baseline that no longer match a finding, see `Baseline::apply`
+    pub fixed_baseline_entries: Vec<BaselineEntry>,
+}
+
+// The kinds of findings. Their ids name them in SARIF rules, baselines, thresholds and configs.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum FindingKind {
+    UnusedExport,
+    UnusedTypeExport,
+    TestOnlyExport,
+    UnusedFile,
+    MissingExport,
+    UnresolvedImport,
+    StaleSuppression,
+}
+
+impl FindingKind {
+    pub const ALL: [FindingKind; 7] = [
+        FindingKind::UnusedExport,
+        FindingKind::UnusedTypeExport,
+        FindingKind::TestOnlyExport,
+        FindingKind::UnusedFile,
+        FindingKind::MissingExport,
+        FindingKind::UnresolvedImport,
+        FindingKind::StaleSuppression,
+    ];
+
+    pub fn id(self) -> &'static str {
+        match self {
+            FindingKind::UnusedExport => "unused-export",
+            FindingKind::UnusedTypeExport => "unused-type-export",
+            FindingKind::TestOnlyExport => "test-only-export",
+            FindingKind::UnusedFile => "unused-file",
+            FindingKind::MissingExport => "missing-export",
+            FindingKind::UnresolvedImport => "unresolved-import",
+            FindingKind::StaleSuppression => "stale-suppression",
+        }
+    }
+
+    pub fn from_id(id: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|kind| kind.id() == id)
+    }
+}
+
+#[derive(Serialize)]
+pub struct FileExport<'a> {
+    pub file: &'a Path,
+    #[serde(flatten)]
+    pub export: &'a UnusedExport,
+}
+
+impl<'a> Report<'a> {
+    pub fn new(results: &'a AnalysisResults) -> Self {
+        let mut files: Vec<_> = results.modules.iter().collect();
+        files.sort_by_key(|(file, _)| *file);
+
+        let mut unused_exports = Vec::new();
+        let mut unused_type_exports = Vec::new();
+        let mut test_only_exports = Vec::new();
+        for (file, module_results) in files {
+            for export in &module_results.unused_exports {
+                unused_exports.push(FileExport { file, export });
+            }
+            for export in &module_results.unused_type_exports {
+                unused_type_exports.push(FileExport { file, export });
+            }
+            for export in &module_results.test_only_exports {
+                test_only_exports.push(FileExport { file, export });
+            }
+        }
+
+        let mut unused_files: Vec<&Path> = results.unused_files.iter().map(|f| &**f).collect();
+        unused_files.sort();
+
+        let mut missing_exports: Vec<&MissingExport> = results.missing_exports.iter().collect();
+        missing_exports
+            .sort_by_key(|missing| (&missing.importer, missing.location, &missing.symbol));
+
+        let mut unresolved_imports: Vec<&UnresolvedImport> =
+            results.unresolved_imports.iter().collect();
+        unresolved_imports.sort_by_key(|unresolved| {
+            (
+                &unresolved.importer,
+                unresolved.location,
+                &unresolved.specifier,
+            )
+        });
+
+        let mut stale_suppressions: Vec<&StaleSuppression> =
+            results.stale_suppressions.iter().collect();
+        stale_suppressions.sort_by_key(|stale| (&stale.file, stale.location));
+
+        let mut diagnostics: Vec<&Diagnostic> = results.diagnostics.iter().collect();
+        diagnostics
+            .sort_by_key(|diagnostic| (&diagnostic.file, diagnostic.severity, &diagnostic.message));
+
+        Self {
+            version: REPORT_VERSION,
+            unused_exports,
+            unused_type_exports,
+            test_only_exports,
+            unused_files,
+            missing_exports,
+            unresolved_imports,
+            stale_suppressions,
+            diagnostics,
+            fixed_baseline_entries: Vec::new(),
+        }
+    }
+
+    // The number of findings of `kind`. Exports that are used in their own file count too, since
+    // they're reported like the others.
+    pub fn count(&self, kind: FindingKind) -> usize {
+        match kind {
+            FindingKind::UnusedExport => self.unused_exports.len(),
+            FindingKind::UnusedTypeExport => self.unused_type_exports.len(),
+            FindingKind::TestOnlyExport => self.test_only_exports.len(),
+            FindingKind::UnusedFile => self.unused_files.len(),
+            FindingKind::MissingExport => self.missing_exports.len(),
+            FindingKind::UnresolvedImport => self.unresolved_imports.len(),
+            FindingKind::StaleSuppression => self.stale_suppressions.len(),
+        }
+    }
+
+    // The file and kind of each finding that `count` counts.
+    pub fn findings(&self) -> Vec<(&Path, FindingKind)> {
+        let mut findings = Vec::new();
+        let exports = [
+            (FindingKind::UnusedExport, &self.unused_exports),
+            (FindingKind::UnusedTypeExport, &self.unused_type_exports),
+        ];
+        for (kind, exports) in exports {
+            findings.extend(exports.iter().map(|unused| (unused.file, kind)));
+        }
+        let test_only = self.test_only_exports.iter();
+        findings.extend(test_only.map(|unused| (unused.file, FindingKind::TestOnlyExport)));
+        let unused_files = self.unused_files.iter();
+        findings.extend(unused_files.map(|file| (*file, FindingKind::UnusedFile)));
+        let missing = self.missing_exports.iter();
+        findings.extend(missing.map(|missing| (&*missing.importer, FindingKind::MissingExport)));
+        let unresolved = self.unresolved_imports.iter();
+        findings.extend(
+            unresolved.map(|unresolved| (&*unresolved.importer, FindingKind::UnresolvedImport)),
+        );
+        let stale = self.stale_suppressions.iter();
+        findings.extend(stale.map(|stale| (&*stale.file, FindingKind::StaleSuppression)));
+        findings
+    }
+
+    pub fn to_json(&self) -> Result<String, String> {
+        serde_json::to_string_pretty(self).map_err(|err| err.to_string())
+    }
+}
+
+// `file` relative to `root` with `/` separators, or `None` if it's outside of `root`.
+pub fn relative_path(root: &Path, file: &Path) -> Option<String> {
+    let relative = file.strip_prefix(root).ok()?;
+    let components: Vec<_> = relative
+        .iter()
+        .map(|component| component.to_string_lossy())
+        .collect();
+    Some(components.join("/"))
+}
@@ -0,0 +1,225 @@
+use std::path::Path;
+
+use serde_json::{json, Value};
+use url::Url;
+use xxhash_rust::xxh3::xxh3_64;
+
+use crate::report::{FindingKind, Report};
+use crate::{Location, Severity};
+
+const SCHEMA: &str = "https://json.schemastore.org/sarif-2.1.0.json";
+
+fn description(kind: FindingKind) -> &'static str {
+    match kind {
+        FindingKind::UnusedExport => "Exported value that no other module uses",
+        FindingKind::UnusedTypeExport => "Exported type that no other module uses",
+        FindingKind::TestOnlyExport => "Export that only tests use",
+        FindingKind::UnusedFile => "File that no other module loads",
+        FindingKind::MissingExport => "Import of a name the imported module doesn't export",
+        FindingKind::UnresolvedImport => "Import that doesn't resolve to a file",
+        FindingKind::StaleSuppression => "Suppression comment that doesn't suppress anything",
+    }
+}
+
+fn level(kind: FindingKind) -> &'static str {
+    match kind {
+        FindingKind::UnresolvedImport => "error",
+        _ => "warning",
+    }
+}
+
+// Builds a SARIF 2.1.0 log of `report`. Paths are made relative to `root`, which is the
+// `SRCROOT` the viewer resolves them against.
+pub fn to_sarif(report: &Report, root: &Path) -> Value {
+    let mut results = Vec::new();
+    let exports = [
+        (FindingKind::UnusedExport, &report.unused_exports),
+        (FindingKind::UnusedTypeExport, &report.unused_type_exports),
+        (FindingKind::TestOnlyExport, &report.test_only_exports),
+    ];
+    for (kind, unused_exports) in exports {
+        for unused in unused_exports {
+            let export = unused.export;
+            let mut name = format!("{:?}", export.exported_name.to_string());
+            if export.exported_name != export.original_name {
+                name += &format!(" (declared as {:?})", export.original_name.to_string());
+            }
+            let (message, level) = match (kind, export.used_in_module) {
+                (FindingKind::TestOnlyExport, _) => {
+                    (format!("{} is only used by tests", name), level(kind))
+                }
+                (_, false) => (format!("{} is never used", name), level(kind)),
+                (_, true) => (
+                    format!(
+                        "{} is only used in this file, so it doesn't need to be exported",
+                        name
+                    ),
+                    "note",
+                ),
+            };
+            let mut result = result(
+                root,
+                kind,
+                unused.file,
+                Some(export.location),
+                message,
+                &export.exported_name,
+            );
+            result["level"] = level.into();
+            results.push(result);
+        }
+    }
+    for file in &report.unused_files {
+        let message = "File is never loaded".into();
+        results.push(result(
+            root,
+            FindingKind::UnusedFile,
+            file,
+            None,
+            message,
+            "",
+        ));
+    }
+    for unresolved in &report.unresolved_imports {
+        results.push(result(
+            root,
+            FindingKind::UnresolvedImport,
+            &unresolved.importer,
+            Some(unresolved.location),
+            format!("Can't resolve {:?}", unresolved.specifier.to_string()),
+            &unresolved.specifier,
+        ));
+    }
+    for missing in &report.missing_exports {
+        let source = artifact_uri(root, &missing.source).0;
+        results.push(result(
+            root,
+            FindingKind::MissingExport,
+            &missing.importer,
+            Some(missing.location),
+            format!(
+                "{:?} isn't exported by {}",
+                missing.symbol.to_string(),
+                source
+            ),
+            &format!("{}\0{}", missing.symbol, source),
+        ));
+    }
+
+    for stale in &report.stale_suppressions {
+        results.push(result(
+            root,
+            FindingKind::StaleSuppression,
+            &stale.file,
+            Some(stale.location),
+            format!("{} doesn't suppress anything", stale.directive),
+            &stale.directive,
+        ));
+    }
+
+    let notifications: Vec<Value> = report
+        .diagnostics
+        .iter()
+        .map(|diagnostic| {
+            json!({
+                "level": match diagnostic.severity {
+                    Severity::Warning => "warning",
+                    Severity::Error => "error",
+                },
+                "message": { "text": diagnostic.message },
+                "locations": [{ "physicalLocation": physical_location(root, &diagnostic.file, None) }],
+            })
+        })
+        .collect();
+
+    let rules: Vec<Value> = FindingKind::ALL
+        .iter()
+        .map(|kind| {
+            json!({
+                "id": kind.id(),
+                "shortDescription": { "text": description(*kind) },
+                "defaultConfiguration": { "level": level(*kind) },
+            })
+        })
+        .collect();
+
+    // Only absolute paths have URIs, and the repo root always is one.
+    let root_uri = Url::from_directory_path(root)
+        .map_or_else(|()| format!("{}/", root.display()), String::from);
+    json!({
+        "$schema": SCHEMA,
+        "version": "2.1.0",
+        "runs": [{
+            "tool": {
+                "driver": {
+                    "name": env!("CARGO_PKG_NAME"),
+                    "version": env!("CARGO_PKG_VERSION"),
+                    "rules": rules,
+                },
+            },
+            "invocations": [{
+                // Files that couldn't be analyzed make the results incomplete.
+                "executionSuccessful": !report
+                    .diagnostics
+                    .iter()
+                    .any(|diagnostic| diagnostic.severity == Severity::Error),
+                "toolExecutionNotifications": notifications,
+            }],
+            "originalUriBaseIds": {
+                "SRCROOT": { "uri": root_uri },
+            },
+            // `Location` counts characters, not UTF-16 code units.
+            "columnKind": "unicodeCodePoints",
+            "results": results,
+        }],
+    })
+}
+
+// `key` identifies the finding within its file, e.g. the exported name. The fingerprint leaves
+// out the line and column, so it survives edits elsewhere in the file.
+fn result(
+    root: &Path,
+    kind: FindingKind,
+    file: &Path,
+    location: Option<Location>,
+    message: String,
+    key: &str,
+) -> Value {
+    let uri = artifact_uri(root, file).0;
+    let fingerprint = xxh3_64(format!("{}\0{}\0{}", kind.id(), uri, key).as_bytes());
+    json!({
+        "ruleId": kind.id(),
+        "ruleIndex": FindingKind::ALL.iter().position(|rule| *rule == kind),
+        "level": level(kind),
+        "message": { "text": message },
+        "locations": [{ "physicalLocation": physical_location(root, file, location) }],
+        "partialFingerprints": { "deadcode/v1": format!("{:016x}", fingerprint) },
+    })
+}
+
+fn physical_location(root: &Path, file: &Path, location: Option<Location>) -> Value {
+    let (uri, relative) = artifact_uri(root, file);
+    let mut physical_location = json!({ "artifactLocation": { "uri": uri } });
+    if relative {
+        physical_location["artifactLocation"]["uriBaseId"] = "SRCROOT".into();
+    }
+    if let Some(location) = location {
+        physical_location["region"] = json!({
+            "startLine": location.line,
+            "startColumn": location.column,
+        });
+    }
+    physical_location
+}
+
+// The URI of `file`, relative to `root` if it's inside it. Both are percent-encoded, e.g. with
+// `%20` for spaces.
+fn artifact_uri(root: &Path, file: &Path) -> (String, bool) {
+    let (Ok(root), Ok(file)) = (Url::from_directory_path(root), Url::from_file_path(file)) else {
+        return (file.display().to_string(), false);
+    };
+    match file.as_str().strip_prefix(root.as_str()) {
+        Some(relative) => (relative.into(), true),
+        None => (file.into(), false),
+    }
+}
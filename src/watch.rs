@@ -0,0 +1,100 @@
+use std::collections::{BTreeSet, HashMap};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use crate::files::FileFilter;
+
+// The size and modification time of every file in a repo that `FileFilter::walk` finds, to
+// notice changes by polling. Ignored files, `node_modules` and `.git` are left out, since they're
+// big and change for unrelated reasons. So are the directories that can't be read, which the
+// analysis warns about.
+#[derive(Debug, Default, PartialEq)]
+pub struct Snapshot {
+    files: HashMap<PathBuf, (u64, SystemTime)>,
+}
+
+impl Snapshot {
+    pub fn take(root: &Path, filter: &FileFilter) -> Self {
+        let mut files = HashMap::new();
+        for file in filter.walk(root).0 {
+            // Files may be deleted while we look at them.
+            let Ok(metadata) = fs::metadata(&file) else {
+                continue;
+            };
+            if let Ok(modified) = metadata.modified() {
+                files.insert(file, (metadata.len(), modified));
+            }
+        }
+        Self { files }
+    }
+
+    // The files that were created, changed or deleted since `self`, sorted.
+    pub fn changes(&self, other: &Snapshot) -> BTreeSet<PathBuf> {
+        let mut changes = BTreeSet::new();
+        for (file, stamp) in &other.files {
+            if self.files.get(file) != Some(stamp) {
+                changes.insert(file.clone());
+            }
+        }
+        for file in self.files.keys() {
+            if !other.files.contains_key(file) {
+                changes.insert(file.clone());
+            }
+        }
+        changes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixture::TempDir;
+
+    #[test]
+    fn changes() {
+        let dir = TempDir::new("watch");
+        dir.write("a.ts", "");
+        dir.write("b.ts", "");
+        let filter = FileFilter::default();
+        let snapshot = || Snapshot::take(dir.path(), &filter);
+        let before = snapshot();
+        assert!(before.changes(&snapshot()).is_empty());
+
+        dir.write("a.ts", "export {};");
+        dir.remove("b.ts");
+        dir.write("c.ts", "");
+        let changes = before.changes(&snapshot());
+        assert_eq!(
+            changes.into_iter().collect::<Vec<_>>(),
+            [dir.join("a.ts"), dir.join("b.ts"), dir.join("c.ts")]
+        );
+    }
+
+    #[test]
+    fn ignored_changes() {
+        let dir = TempDir::new("watch-ignored");
+        dir.write(".gitignore", "dist/\n");
+        dir.write("dist/a.js", "");
+        dir.write("generated/a.ts", "");
+        dir.write("node_modules/dep.js", "");
+        let filter = FileFilter {
+            include: vec!["src".into()],
+            exclude: vec!["generated".into()],
+        };
+        let snapshot = || Snapshot::take(dir.path(), &filter);
+        let before = snapshot();
+
+        dir.write("dist/a.js", "exports.a = 1;");
+        dir.write("generated/a.ts", "export {};");
+        dir.write("node_modules/dep.js", "module.exports = {};");
+        assert!(before.changes(&snapshot()).is_empty());
+
+        // Files that aren't included may still matter, e.g. package.jsons.
+        dir.write("package.json", "{}");
+        assert_eq!(
+            before.changes(&snapshot()).into_iter().collect::<Vec<_>>(),
+            [dir.join("package.json")]
+        );
+    }
+}
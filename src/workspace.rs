@@ -0,0 +1,357 @@
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde_json::Value;
+
+use crate::{Diagnostic, Severity};
+
+// Returns the package directories of the workspace in `root`, sorted and including the root
+// itself. Packages are the directories in `package_dirs`, those with a package.json, matched by
+// the `workspaces` of the root package.json or the `packages` of pnpm-workspace.yaml, and the
+// projects the root tsconfig.json references, directly or through other references.
+//
+// Returns `None` if `root` doesn't define a workspace, in which case every package.json is
+// its own package.
+pub fn workspace_packages(
+    root: &Path,
+    package_dirs: &[PathBuf],
+) -> Result<Option<Vec<PathBuf>>, Diagnostic> {
+    let mut patterns = package_json_workspaces(&root.join("package.json"))?;
+    patterns.extend(pnpm_workspace_packages(&root.join("pnpm-workspace.yaml"))?);
+    let mut references = HashSet::new();
+    tsconfig_references(&root.join("tsconfig.json"), &mut references)?;
+    if patterns.is_empty() && references.is_empty() {
+        return Ok(None);
+    }
+
+    let mut packages: Vec<PathBuf> = package_dirs
+        .iter()
+        .filter(|dir| {
+            let relative_path = dir.strip_prefix(root).unwrap_or(dir);
+            is_workspace_package(&patterns, &relative_path.to_string_lossy())
+        })
+        .cloned()
+        .collect();
+    packages.extend(references);
+    packages.push(root.into());
+    packages.sort();
+    packages.dedup();
+    Ok(Some(packages))
+}
+
+// Whether the last of `patterns` matching the directory includes rather than excludes it, e.g.
+// `packages/*` followed by `!packages/legacy`.
+fn is_workspace_package(patterns: &[String], relative_path: &str) -> bool {
+    let mut included = false;
+    for pattern in patterns {
+        let (negated, pattern) = match pattern.strip_prefix('!') {
+            Some(pattern) => (true, pattern),
+            None => (false, pattern.as_str()),
+        };
+        let pattern = pattern.trim_start_matches("./").trim_end_matches('/');
+        if pattern == relative_path || glob_match::glob_match(pattern, relative_path) {
+            included = !negated;
+        }
+    }
+    included
+}
+
+// `"workspaces": [...]`, or `"workspaces": { "packages": [...] }` as used by Yarn.
+fn package_json_workspaces(path: &Path) -> Result<Vec<String>, Diagnostic> {
+    let Ok(contents) = fs::read_to_string(path) else {
+        return Ok(Vec::new());
+    };
+    let package: Value = serde_json::from_str(&contents)
+        .map_err(|err| warning(path, format!("invalid package.json: {}", err)))?;
+    let workspaces = match package.get("workspaces") {
+        Some(Value::Object(workspaces)) => workspaces.get("packages"),
+        workspaces => workspaces,
+    };
+    match workspaces {
+        None => Ok(Vec::new()),
+        Some(Value::Array(patterns)) => patterns
+            .iter()
+            .map(|pattern| match pattern {
+                Value::String(pattern) => Ok(pattern.clone()),
+                _ => Err(warning(path, "workspaces should be strings".into())),
+            })
+            .collect(),
+        Some(_) => Err(warning(path, "workspaces should be an array".into())),
+    }
+}
+
+// The `packages` list of a pnpm-workspace.yaml. Only the block and flow sequences pnpm's docs
+// use are supported, not YAML in general.
+fn pnpm_workspace_packages(path: &Path) -> Result<Vec<String>, Diagnostic> {
+    let Ok(contents) = fs::read_to_string(path) else {
+        return Ok(Vec::new());
+    };
+    let unquote = |value: &str| {
+        let value = value.trim();
+        value
+            .strip_prefix('\'')
+            .and_then(|value| value.strip_suffix('\''))
+            .or_else(|| {
+                value
+                    .strip_prefix('"')
+                    .and_then(|value| value.strip_suffix('"'))
+            })
+            .unwrap_or(value)
+            .to_owned()
+    };
+
+    let mut packages = Vec::new();
+    let mut in_packages = false;
+    for line in contents.lines() {
+        let line = match line.find('#') {
+            Some(comment) if comment == 0 || line[..comment].ends_with([' ', '\t']) => {
+                &line[..comment]
+            }
+            _ => line,
+        };
+        if line.trim().is_empty() {
+            continue;
+        }
+        // Items may be indented under `packages:` or not.
+        if let Some(item) = line.trim_start().strip_prefix('-') {
+            if in_packages {
+                packages.push(unquote(item));
+            }
+            continue;
+        }
+        if line.starts_with([' ', '\t']) {
+            continue;
+        }
+        in_packages = false;
+        if let Some(value) = line.strip_prefix("packages:") {
+            let value = value.trim();
+            if value.is_empty() {
+                in_packages = true;
+            } else if let Some(items) = value
+                .strip_prefix('[')
+                .and_then(|value| value.strip_suffix(']'))
+            {
+                packages.extend(
+                    items
+                        .split(',')
+                        .filter(|item| !item.trim().is_empty())
+                        .map(unquote),
+                );
+            } else {
+                return Err(warning(path, "packages should be a list".into()));
+            }
+        }
+    }
+    Ok(packages)
+}
+
+// Adds the directories of the projects referenced by the tsconfig at `path` to `references`,
+// following their references in turn. A reference is either a directory with a tsconfig.json,
+// or a tsconfig file.
+fn tsconfig_references(path: &Path, references: &mut HashSet<PathBuf>) -> Result<(), Diagnostic> {
+    let Ok(mut contents) = fs::read_to_string(path) else {
+        return Ok(());
+    };
+    // tsconfigs may have comments and trailing commas.
+    let _ = json_comments::strip_comments_in_place(&mut contents, Default::default(), true);
+    let tsconfig: Value = serde_json::from_str(&contents)
+        .map_err(|err| warning(path, format!("invalid tsconfig: {}", err)))?;
+    let Some(Value::Array(project_references)) = tsconfig.get("references") else {
+        return Ok(());
+    };
+
+    let dir = path.parent().unwrap_or(Path::new(""));
+    for reference in project_references {
+        let Some(Value::String(reference)) = reference.get("path") else {
+            return Err(warning(path, "references should have a path".into()));
+        };
+        let target = dir.join(reference);
+        let (project, tsconfig) = match target.is_file() {
+            true => (target.parent().unwrap().to_path_buf(), target.clone()),
+            false => (target.clone(), target.join("tsconfig.json")),
+        };
+        // References may form cycles, or share projects.
+        let project = fs::canonicalize(&project).unwrap_or(project);
+        if references.insert(project) {
+            tsconfig_references(&tsconfig, references)?;
+        }
+    }
+    Ok(())
+}
+
+fn warning(file: &Path, message: String) -> Diagnostic {
+    Diagnostic {
+        severity: Severity::Warning,
+        file: file.into(),
+        message,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixture::TempDir;
+
+    #[test]
+    fn packages() {
+        let dir = TempDir::new("workspace");
+        let packages = [
+            "",
+            "apps/web",
+            "packages/a",
+            "packages/a/test/fixture",
+            "packages/legacy",
+        ];
+        for package in packages {
+            dir.write(Path::new(package).join("package.json"), "{}");
+        }
+        let package_dirs = packages.map(|package| dir.join(package));
+        assert_eq!(workspace_packages(dir.path(), &package_dirs), Ok(None));
+
+        for (file, contents) in [
+            (
+                "package.json",
+                r#"{ "workspaces": ["packages/*", "!packages/legacy"] }"#,
+            ),
+            (
+                "pnpm-workspace.yaml",
+                "# pnpm\npackages:\n  - 'apps/*' # apps\n",
+            ),
+            (
+                "tsconfig.json",
+                "{\n  // projects\n  \"references\": [{ \"path\": \"./tools/build\" },],\n}",
+            ),
+            (
+                "tools/build/tsconfig.json",
+                r#"{ "references": [{ "path": "../lint/tsconfig.lib.json" }, { "path": "../.." }] }"#,
+            ),
+            ("tools/lint/tsconfig.lib.json", "{}"),
+        ] {
+            dir.write(file, contents);
+        }
+        assert_eq!(
+            workspace_packages(dir.path(), &package_dirs),
+            Ok(Some(
+                ["", "apps/web", "packages/a", "tools/build", "tools/lint"]
+                    .map(|package| dir.join(package))
+                    .to_vec()
+            ))
+        );
+
+        dir.write("package.json", r#"{ "workspaces": "packages/*" }"#);
+        assert_eq!(
+            workspace_packages(dir.path(), &package_dirs)
+                .unwrap_err()
+                .file,
+            dir.join("package.json")
+        );
+    }
+
+    #[test]
+    fn negations() {
+        let patterns = [
+            "packages/*",
+            "!packages/legacy*",
+            "./packages/legacy-tools/",
+        ]
+        .map(String::from);
+        assert!(is_workspace_package(&patterns, "packages/a"));
+        assert!(!is_workspace_package(&patterns, "packages/legacy"));
+        // The last matching pattern wins.
+        assert!(is_workspace_package(&patterns, "packages/legacy-tools"));
+        assert!(!is_workspace_package(&patterns, "packages/a/test"));
+        assert!(!is_workspace_package(&patterns[1..], "packages/a"));
+    }
+
+    #[test]
+    fn package_json() {
+        let dir = TempDir::new("workspace-package-json");
+        let workspaces = |contents: &str| {
+            dir.write("package.json", contents);
+            package_json_workspaces(&dir.join("package.json"))
+        };
+        assert_eq!(workspaces("{}"), Ok(Vec::new()));
+        assert_eq!(
+            workspaces(r#"{ "workspaces": ["packages/*"] }"#),
+            Ok(vec!["packages/*".into()])
+        );
+        assert_eq!(
+            workspaces(
+                r#"{ "workspaces": { "packages": ["packages/*"], "nohoist": ["**/react"] } }"#
+            ),
+            Ok(vec!["packages/*".into()])
+        );
+        assert!(workspaces(r#"{ "workspaces": [1] }"#).is_err());
+        assert!(workspaces(r#"{ "workspaces": "packages/*" }"#).is_err());
+        assert!(workspaces("{").is_err());
+        assert_eq!(
+            package_json_workspaces(&dir.join("missing/package.json")),
+            Ok(Vec::new())
+        );
+    }
+
+    #[test]
+    fn pnpm_workspace() {
+        let dir = TempDir::new("workspace-pnpm");
+        let packages = |contents: &str| {
+            dir.write("pnpm-workspace.yaml", contents);
+            pnpm_workspace_packages(&dir.join("pnpm-workspace.yaml"))
+        };
+        let block =
+            "# The packages\npackages:\n  - 'apps/*' # apps\n\n  - \"packages/*\"\n  - tools#1\n";
+        assert_eq!(
+            packages(block),
+            Ok(vec!["apps/*".into(), "packages/*".into(), "tools#1".into()])
+        );
+        // Items don't have to be indented, and other lists aren't packages.
+        assert_eq!(
+            packages(
+                "packages:\n- apps/*\n- '!apps/legacy'\nonlyBuiltDependencies:\n  - esbuild\n"
+            ),
+            Ok(vec!["apps/*".into(), "!apps/legacy".into()])
+        );
+        assert_eq!(
+            packages("packages: ['apps/*', \"packages/*\",]\ncatalog:\n  react: ^18\n"),
+            Ok(vec!["apps/*".into(), "packages/*".into()])
+        );
+        assert_eq!(packages("packages: []\n"), Ok(Vec::new()));
+        assert!(packages("packages: apps/*\n").is_err());
+    }
+
+    #[test]
+    fn references() {
+        let dir = TempDir::new("workspace-references");
+        dir.write(
+            "tsconfig.json",
+            r#"{ "references": [{ "path": "./a" }, { "path": "b/tsconfig.build.json" }] }"#,
+        );
+        // References back to the root, and between the projects
+        dir.write(
+            "a/tsconfig.json",
+            r#"{ "references": [{ "path": ".." }, { "path": "../b/tsconfig.build.json" }] }"#,
+        );
+        dir.write(
+            "b/tsconfig.build.json",
+            r#"{ "references": [{ "path": "../c" }] }"#,
+        );
+        // c has no tsconfig, which TypeScript reports rather than us.
+        dir.write("c/index.ts", "");
+        let mut references = HashSet::new();
+        tsconfig_references(&dir.join("tsconfig.json"), &mut references).unwrap();
+        assert_eq!(
+            references,
+            ["", "a", "b", "c"].map(|project| dir.join(project)).into()
+        );
+
+        dir.write(
+            "b/tsconfig.build.json",
+            r#"{ "references": [{ "prepend": true }] }"#,
+        );
+        let err = tsconfig_references(&dir.join("tsconfig.json"), &mut HashSet::new()).unwrap_err();
+        assert_eq!(
+            fs::canonicalize(err.file).unwrap(),
+            dir.join("b/tsconfig.build.json")
+        );
+    }
+}
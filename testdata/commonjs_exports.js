@@ -0,0 +1 @@
+exports.helper = function helper() {};
@@ -0,0 +1,17 @@
+// `recursive` is only mentioned in comments and strings outside of itself.
+export function helper() {}
+
+export function recursive(n: number): number {
+  return n ? recursive(n - 1) : 0;
+}
+
+export const value = helper();
+
+export interface Options {}
+
+export default function main(options: Options) {
+  return value;
+}
+
+const label = "recursive";
+main({});
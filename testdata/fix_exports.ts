@@ -0,0 +1,19 @@
+/** Nothing uses this. */
+export const dead = 1;
+export const a = 1, b = 2;
+export const [A, B] = [1, 2];
+export const { O1, O2 } = { O1: 1, O2: 2 };
+export function helper() {}
+helper();
+export const store = createStore();
+export interface Unused {}
+export const keep = 1;
+const spec1 = 1, spec2 = 2, spec3 = 3;
+export {
+  spec1,
+  spec2,
+  spec3,
+};
+export { spec1 as gone };
+export default function main() {}
+declare function createStore(): number;
@@ -0,0 +1,3 @@
+import { a, B, keep, D2, O2, spec2 } from './fix_exports';
+
+console.log(a, B, keep, D2, O2, spec2);
@@ -0,0 +1,12 @@
+import { Class, Missing } from './export_named';
+import * as utils from './export_named';
+import { helper } from './commonjs_exports';
+import { Gone } from './reexport_missing';
+import { foo } from './export_foo';
+
+Class;
+Missing;
+utils.AlsoMissing;
+helper;
+Gone;
+foo;
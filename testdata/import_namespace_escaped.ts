@@ -0,0 +1,3 @@
+import * as utils from './export_named';
+
+console.log(utils);
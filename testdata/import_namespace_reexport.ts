@@ -0,0 +1,5 @@
+import { Namespace } from './export_namespace';
+
+Namespace.Class;
+const {Fn} = Namespace;
+const value: Namespace.Interface = {};
@@ -0,0 +1,4 @@
+import * as barrel from './export_namespace';
+
+barrel.Namespace.Class;
+console.log(barrel.Namespace);
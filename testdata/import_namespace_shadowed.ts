@@ -0,0 +1,33 @@
+import * as utils from './export_named';
+
+function shadowedByParam(utils: any) {
+  return utils.Class;
+}
+
+function shadowedByLocal() {
+  const utils = {Fn: () => {}};
+  return utils.Fn;
+}
+
+function shadowedByHoistedVar() {
+  if (true) {
+    var utils = {Type: 1};
+  }
+  return utils.Type;
+}
+
+{
+  const utils = {Enum: 1};
+  utils.Enum;
+}
+
+try {} catch (utils) {
+  utils.Const;
+}
+
+function unshadowed() {
+  return utils.Var;
+}
+
+const alias = utils;
+alias.Interface;
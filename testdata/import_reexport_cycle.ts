@@ -0,0 +1 @@
+import { cycle } from './reexport_cycle_a';
@@ -0,0 +1 @@
+import { foo, DefaultClass } from './reexport_named_again';
@@ -0,0 +1,3 @@
+import { thing } from './does_not_exist';
+
+export const value = thing;
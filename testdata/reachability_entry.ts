@@ -0,0 +1,4 @@
+import './reachability_side_effect';
+import { used } from './reachability_lib';
+
+export const api = used();
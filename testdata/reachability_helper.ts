@@ -0,0 +1,7 @@
+export function helper() {}
+
+export function deadHelper() {}
+
+export function orphanHelper() {}
+
+export function sideEffectHelper() {}
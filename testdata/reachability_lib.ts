@@ -0,0 +1,9 @@
+import { helper, deadHelper } from './reachability_helper';
+
+export function used() {
+  return helper();
+}
+
+export function unused() {
+  return deadHelper();
+}
@@ -0,0 +1,5 @@
+import { orphanHelper } from './reachability_helper';
+
+export function orphan() {
+  orphanHelper();
+}
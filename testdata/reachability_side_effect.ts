@@ -0,0 +1,3 @@
+import { sideEffectHelper } from './reachability_helper';
+
+sideEffectHelper();
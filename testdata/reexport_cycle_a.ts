@@ -0,0 +1,2 @@
+export { cycle } from './reexport_cycle_b';
+export const notCycle = 1;
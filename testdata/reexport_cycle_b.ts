@@ -0,0 +1 @@
+export { cycle } from './reexport_cycle_a';
@@ -0,0 +1 @@
+export { Gone } from './export_named';
@@ -0,0 +1,2 @@
+export { foo as renamedFoo, bar } from './export_foo';
+export { default as DefaultClass } from './export_default_class';
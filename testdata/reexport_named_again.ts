@@ -0,0 +1 @@
+export { renamedFoo as foo, DefaultClass } from './reexport_named';
@@ -0,0 +1,5 @@
+// Calls we can't follow shouldn't stop the analysis.
+const x = require();
+console.log(x);
+
+export const Value = 1;
@@ -0,0 +1,16 @@
+// ts-deadcode-ignore-next-line
+import './does_not_exist';
+
+// ts-deadcode-ignore-next-line
+export const Ignored = 1;
+export const Reported = 2;
+
+/**
+ * Loaded by the plugin system.
+ * @public
+ */
+export function Public() {}
+
+// ts-deadcode-ignore-next-line: nothing to suppress anymore
+const local = 3;
+console.log(local);
@@ -0,0 +1,2 @@
+/* ts-deadcode-disable */
+export const Disabled = 1;